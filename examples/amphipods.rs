@@ -0,0 +1,18 @@
+// Play the day 23 amphipod puzzle in the terminal.
+// Usage: cargo run --example amphipods -- [input file] [--unfold]
+
+#![allow(clippy::bool_comparison)]
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let unfold = args.iter().any(|a| a == "--unfold");
+    let path = args
+        .iter()
+        .find(|a| a.starts_with("--") == false)
+        .map_or("input/2021/day23.txt", String::as_str);
+
+    let input = std::fs::read_to_string(path)?;
+    advent_of_code_2021::play_amphipods(&input, unfold, io::stdin().lock(), io::stdout())
+}
@@ -0,0 +1,13 @@
+// Write the assembled day 19 beacon map as CSV, PLY and OBJ files for viewing in other tools.
+// Usage: cargo run --example beacon_map -- [input file] [output directory]
+
+use std::{io, path::Path};
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = args.first().map_or("input/2021/day19.txt", String::as_str);
+    let dir = args.get(1).map_or(".", String::as_str);
+
+    let input = std::fs::read_to_string(path)?;
+    advent_of_code_2021::export_beacon_map(&input, Path::new(dir))
+}
@@ -0,0 +1,18 @@
+// Print every generation of the day 25 sea cucumbers until they stop moving. Best kept to small floors.
+// Usage: cargo run --example sea_cucumbers -- [input file] [step limit]
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = args.first().map_or("input/2021/day25.txt", String::as_str);
+    let step_limit = match args.get(1) {
+        Some(arg) => arg.parse().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Step limit must be a number")
+        })?,
+        None => 1000,
+    };
+
+    let input = std::fs::read_to_string(path)?;
+    advent_of_code_2021::animate_sea_cucumbers(&input, step_limit, &mut io::stdout().lock())
+}
@@ -0,0 +1,504 @@
+use crate::common::{modulo, Point2, TileChar};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Neighborhood {
+    Moore,               // The 8 surrounding cells
+    VonNeumann,          // The 4 orthogonally adjacent cells
+    Custom(Vec<Point2>), // Any offsets at all, including the cell itself
+}
+
+impl Neighborhood {
+    // The rule sees the neighbors in this order
+    pub fn offsets(&self) -> Vec<Point2> {
+        match self {
+            Self::Moore => [
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ]
+            .into_iter()
+            .map(Point2::from)
+            .collect(),
+            Self::VonNeumann => [(0, -1), (-1, 0), (1, 0), (0, 1)]
+                .into_iter()
+                .map(Point2::from)
+                .collect(),
+            Self::Custom(offsets) => offsets.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Boundary<T> {
+    Fixed(T),    // Everything outside the grid is this value, forever
+    Infinite(T), // The grid sits in an infinite plane of this value, which follows the rule too. The grid resizes to fit.
+    Wrapping,    // Leaving one edge comes back in at the other
+}
+
+pub trait Rule {
+    type Cell: Clone + PartialEq;
+
+    fn neighborhood(&self) -> Neighborhood;
+    fn next(&self, cell: &Self::Cell, neighbors: &[Self::Cell]) -> Self::Cell;
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,  // Row-major
+    origin: Point2, // The coordinates of the top left cell, which moves as an infinite grid grows
+    boundary: Boundary<T>,
+}
+
+impl<T: Clone + PartialEq> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T, boundary: Boundary<T>) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![fill; width * height],
+            origin: Point2::origin(),
+            boundary,
+        }
+    }
+
+    pub fn from_rows(rows: Vec<Vec<T>>, boundary: Boundary<T>) -> Self {
+        let height = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+        assert!(
+            rows.iter().all(|r| r.len() == width),
+            "Rows must all be the same length"
+        );
+        Self {
+            width,
+            height,
+            cells: rows.into_iter().flatten().collect(),
+            origin: Point2::origin(),
+            boundary,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn origin(&self) -> Point2 {
+        self.origin
+    }
+
+    pub fn boundary(&self) -> &Boundary<T> {
+        &self.boundary
+    }
+
+    fn index(&self, p: Point2) -> Option<usize> {
+        let x = p.x - self.origin.x;
+        let y = p.y - self.origin.y;
+        if self.width == 0 || self.height == 0 {
+            None
+        } else if self.boundary == Boundary::Wrapping {
+            let x = modulo(x, self.width as i32) as usize;
+            let y = modulo(y, self.height as i32) as usize;
+            Some(y * self.width + x)
+        } else if 0 <= x && (x as usize) < self.width && 0 <= y && (y as usize) < self.height {
+            Some(y as usize * self.width + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, p: Point2) -> &T {
+        match (self.index(p), &self.boundary) {
+            (Some(idx), _) => &self.cells[idx],
+            (None, Boundary::Fixed(value) | Boundary::Infinite(value)) => value,
+            (None, Boundary::Wrapping) => panic!("Can't look up a point in an empty grid"),
+        }
+    }
+
+    pub fn set(&mut self, p: Point2, value: T) {
+        let idx = self.index(p).expect("Point is outside the grid");
+        self.cells[idx] = value;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point2, &T)> + '_ {
+        self.cells.iter().enumerate().map(|(i, cell)| {
+            let p = Point2 {
+                x: self.origin.x + (i % self.width) as i32,
+                y: self.origin.y + (i / self.width) as i32,
+            };
+            (p, cell)
+        })
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> + '_ {
+        self.cells.chunks(self.width.max(1)).take(self.height)
+    }
+
+    // Update every cell at once. Returns whether anything changed.
+    pub fn step<R: Rule<Cell = T>>(&mut self, rule: &R) -> bool {
+        let offsets = rule.neighborhood().offsets();
+
+        // An infinite grid grows by the reach of the neighborhood, since that's as far as the effect of the
+        // non-background cells can spread.
+        let grow = match self.boundary {
+            Boundary::Infinite(_) => offsets
+                .iter()
+                .map(|o| o.x.abs().max(o.y.abs()))
+                .max()
+                .unwrap_or(0) as usize,
+            _ => 0,
+        };
+        let width = self.width + 2 * grow;
+        let height = self.height + 2 * grow;
+        let origin = self.origin - (grow as i32, grow as i32);
+
+        let mut changed = false;
+        let mut cells = Vec::with_capacity(width * height);
+        let mut neighbors = Vec::with_capacity(offsets.len());
+        for y in 0..height {
+            for x in 0..width {
+                let p = origin + (x as i32, y as i32);
+                neighbors.clear();
+                neighbors.extend(offsets.iter().map(|o| self.get(p + o).clone()));
+                let cell = self.get(p);
+                let next = rule.next(cell, &neighbors);
+                if next != *cell {
+                    changed = true;
+                }
+                cells.push(next);
+            }
+        }
+
+        if let Boundary::Infinite(background) = &mut self.boundary {
+            let uniform = vec![background.clone(); offsets.len()];
+            let next = rule.next(background, &uniform);
+            if next != *background {
+                changed = true;
+            }
+            *background = next;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.origin = origin;
+        self.cells = cells;
+        self.trim();
+        changed
+    }
+
+    // Shrink an infinite grid back down to the cells that differ from the background. Otherwise it would grow every
+    // step and a pattern that has settled would never compare equal to an earlier step.
+    fn trim(&mut self) {
+        let background = match &self.boundary {
+            Boundary::Infinite(value) => value,
+            _ => return,
+        };
+
+        let mut min = (usize::MAX, usize::MAX);
+        let mut max = (0, 0);
+        for (i, cell) in self.cells.iter().enumerate() {
+            if cell != background {
+                let (x, y) = (i % self.width, i / self.width);
+                min = (min.0.min(x), min.1.min(y));
+                max = (max.0.max(x), max.1.max(y));
+            }
+        }
+
+        if min.0 == usize::MAX {
+            // Nothing but background
+            self.width = 0;
+            self.height = 0;
+            self.origin = Point2::origin();
+            self.cells.clear();
+            return;
+        }
+
+        let width = max.0 - min.0 + 1;
+        let height = max.1 - min.1 + 1;
+        let mut cells = Vec::with_capacity(width * height);
+        for y in min.1..=max.1 {
+            let start = y * self.width + min.0;
+            cells.extend_from_slice(&self.cells[start..start + width]);
+        }
+        self.width = width;
+        self.height = height;
+        self.origin += (min.0 as i32, min.1 as i32);
+        self.cells = cells;
+    }
+}
+
+impl<T: Clone + PartialEq + TileChar> Grid<T> {
+    pub fn from_string(input: &str, boundary: Boundary<T>) -> Self {
+        let rows = input
+            .lines()
+            .map(|line| line.chars().filter_map(T::from_char).collect())
+            .collect();
+        Self::from_rows(rows, boundary)
+    }
+}
+
+impl<T: TileChar> std::fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.cells.chunks(self.width.max(1)).take(self.height) {
+            for cell in row {
+                write!(f, "{}", cell.to_char())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RunResult {
+    FixedPoint(usize), // The (1-based) step that first changed nothing
+    Cycle { start: usize, length: usize }, // The state after step 'start' comes back every 'length' steps
+    StepLimit,
+}
+
+// Keep stepping until the state stops changing or repeats an earlier one, giving up after the step limit
+pub fn run_until_repeat<S, F>(state: &mut S, step_limit: usize, mut step: F) -> RunResult
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&mut S),
+{
+    let mut seen: HashMap<S, usize> = HashMap::new();
+    seen.insert(state.clone(), 0);
+    for n in 1..=step_limit {
+        step(state);
+        if let Some(prev) = seen.get(state) {
+            if *prev == n - 1 {
+                return RunResult::FixedPoint(n);
+            } else {
+                return RunResult::Cycle {
+                    start: *prev,
+                    length: n - prev,
+                };
+            }
+        }
+        seen.insert(state.clone(), n);
+    }
+    RunResult::StepLimit
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Life {
+        Alive,
+        Dead,
+    }
+
+    impl TileChar for Life {
+        fn to_char(&self) -> char {
+            match self {
+                Self::Alive => '#',
+                Self::Dead => '.',
+            }
+        }
+
+        fn from_char(c: char) -> Option<Self> {
+            match c {
+                '#' => Some(Self::Alive),
+                '.' => Some(Self::Dead),
+                _ => None,
+            }
+        }
+
+        fn all_chars() -> Vec<char> {
+            vec!['#', '.']
+        }
+    }
+
+    struct Conway;
+
+    impl Rule for Conway {
+        type Cell = Life;
+
+        fn neighborhood(&self) -> Neighborhood {
+            Neighborhood::Moore
+        }
+
+        fn next(&self, cell: &Life, neighbors: &[Life]) -> Life {
+            let alive = neighbors.iter().filter(|n| **n == Life::Alive).count();
+            match (cell, alive) {
+                (Life::Alive, 2 | 3) | (Life::Dead, 3) => Life::Alive,
+                _ => Life::Dead,
+            }
+        }
+    }
+
+    // Counts how many neighbors are set, or flips everything if there are none
+    struct Count(Neighborhood);
+
+    impl Rule for Count {
+        type Cell = usize;
+
+        fn neighborhood(&self) -> Neighborhood {
+            self.0.clone()
+        }
+
+        fn next(&self, _cell: &usize, neighbors: &[usize]) -> usize {
+            match neighbors.iter().filter(|n| **n != 0).count() {
+                0 => 1,
+                x => x,
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighborhood() {
+        assert_eq!(Neighborhood::Moore.offsets().len(), 8);
+        assert_eq!(Neighborhood::VonNeumann.offsets().len(), 4);
+        let custom = vec![(0, 0).into(), (2, 0).into()];
+        assert_eq!(Neighborhood::Custom(custom.clone()).offsets(), custom);
+
+        let mut grid = Grid::new(3, 3, 0, Boundary::Fixed(0));
+        grid.set((1, 1).into(), 1);
+        grid.step(&Count(Neighborhood::VonNeumann));
+        let rows: Vec<&[usize]> = grid.rows().collect();
+        assert_eq!(rows, vec![[1, 1, 1], [1, 1, 1], [1, 1, 1]]);
+
+        let mut grid = Grid::new(3, 3, 0, Boundary::Fixed(0));
+        grid.set((1, 1).into(), 1);
+        grid.step(&Count(Neighborhood::Moore));
+        let rows: Vec<&[usize]> = grid.rows().collect();
+        assert_eq!(rows, vec![[1, 1, 1], [1, 1, 1], [1, 1, 1]]);
+        grid.step(&Count(Neighborhood::Moore));
+        let rows: Vec<&[usize]> = grid.rows().collect();
+        assert_eq!(rows, vec![[3, 5, 3], [5, 8, 5], [3, 5, 3]]);
+    }
+
+    #[test]
+    fn test_fixed_boundary() {
+        let mut grid = Grid::<Life>::from_string(
+            ".....\n..#..\n..#..\n..#..\n.....",
+            Boundary::Fixed(Life::Dead),
+        );
+        assert_eq!(grid.step(&Conway), true);
+        assert_eq!(grid.to_string(), ".....\n.....\n.###.\n.....\n.....\n");
+        assert_eq!(grid.step(&Conway), true);
+        assert_eq!(grid.to_string(), ".....\n..#..\n..#..\n..#..\n.....\n");
+
+        // A block never changes
+        let mut grid = Grid::<Life>::from_string("##\n##", Boundary::Fixed(Life::Dead));
+        assert_eq!(grid.step(&Conway), false);
+        assert_eq!(grid.get((5, 5).into()), &Life::Dead);
+    }
+
+    #[test]
+    fn test_wrapping_boundary() {
+        let mut grid = Grid::<Life>::from_string("...\n###\n...", Boundary::Wrapping);
+        assert_eq!(grid.get((-1, 1).into()), &Life::Alive);
+        assert_eq!(grid.get((4, -2).into()), &Life::Alive);
+
+        // Every cell sees all 3 live cells and lives
+        grid.step(&Conway);
+        assert_eq!(grid.to_string(), "###\n###\n###\n");
+    }
+
+    #[test]
+    fn test_infinite_boundary() {
+        let mut grid = Grid::<Life>::from_string("###", Boundary::Infinite(Life::Dead));
+        grid.step(&Conway);
+        assert_eq!(grid.width(), 1);
+        assert_eq!(grid.height(), 3);
+        assert_eq!(grid.origin(), (1, -1).into());
+        assert_eq!(grid.to_string(), "#\n#\n#\n");
+        assert_eq!(grid.get((1, -1).into()), &Life::Alive);
+        assert_eq!(grid.get((0, 0).into()), &Life::Dead);
+        assert_eq!(grid.get((100, 100).into()), &Life::Dead);
+
+        // The background follows the rule too
+        let mut grid = Grid::new(1, 1, 0, Boundary::Infinite(0));
+        assert_eq!(grid.step(&Count(Neighborhood::VonNeumann)), true);
+        assert_eq!(grid.boundary(), &Boundary::Infinite(1));
+        assert_eq!(grid.get((-100, 0).into()), &1);
+        assert_eq!(grid.width(), 0);
+    }
+
+    #[test]
+    fn test_run_until_repeat() {
+        // Blinker
+        let mut grid = Grid::<Life>::from_string(
+            ".....\n..#..\n..#..\n..#..\n.....",
+            Boundary::Fixed(Life::Dead),
+        );
+        let result = run_until_repeat(&mut grid, 10, |g| {
+            g.step(&Conway);
+        });
+        assert_eq!(
+            result,
+            RunResult::Cycle {
+                start: 0,
+                length: 2
+            }
+        );
+
+        // Glider on a torus comes back to where it started after moving all the way around
+        let mut grid = Grid::<Life>::from_string(
+            ".#....\n..#...\n###...\n......\n......\n......",
+            Boundary::Wrapping,
+        );
+        let result = run_until_repeat(&mut grid, 100, |g| {
+            g.step(&Conway);
+        });
+        assert_eq!(
+            result,
+            RunResult::Cycle {
+                start: 0,
+                length: 24
+            }
+        );
+
+        // Dies out, then stays dead
+        let mut grid = Grid::<Life>::from_string("#..\n...\n..#", Boundary::Fixed(Life::Dead));
+        let result = run_until_repeat(&mut grid, 10, |g| {
+            g.step(&Conway);
+        });
+        assert_eq!(result, RunResult::FixedPoint(2));
+
+        // An infinite grid only keeps what differs from the background, so it can settle too
+        let mut grid = Grid::<Life>::from_string("##\n##", Boundary::Infinite(Life::Dead));
+        let result = run_until_repeat(&mut grid, 10, |g| {
+            g.step(&Conway);
+        });
+        assert_eq!(result, RunResult::FixedPoint(1));
+
+        let mut grid = Grid::<Life>::from_string("###", Boundary::Infinite(Life::Dead));
+        let result = run_until_repeat(&mut grid, 10, |g| {
+            g.step(&Conway);
+        });
+        assert_eq!(
+            result,
+            RunResult::Cycle {
+                start: 0,
+                length: 2
+            }
+        );
+
+        // Dies out completely
+        let mut grid = Grid::<Life>::from_string("#.\n.#", Boundary::Infinite(Life::Dead));
+        let result = run_until_repeat(&mut grid, 10, |g| {
+            g.step(&Conway);
+        });
+        assert_eq!(result, RunResult::FixedPoint(2));
+        assert_eq!(grid.width(), 0);
+
+        let mut n = 0;
+        let result = run_until_repeat(&mut n, 10, |n| *n += 1);
+        assert_eq!(result, RunResult::StepLimit);
+    }
+}
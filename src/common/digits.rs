@@ -0,0 +1,233 @@
+use num::Integer;
+use std::ops::RangeInclusive;
+
+// The digits of n, most significant first. Zero is the single digit 0.
+pub fn to_digits<T>(n: T, base: T) -> Vec<T>
+where
+    T: Copy + Integer,
+{
+    assert!(n >= T::zero());
+    assert!(base > T::one());
+
+    let mut digits = Vec::new();
+    let mut rest = n;
+    loop {
+        let (div, rem) = rest.div_rem(&base);
+        digits.push(rem);
+        rest = div;
+        if rest == T::zero() {
+            break;
+        }
+    }
+    digits.reverse();
+    digits
+}
+
+// Write the digits of n into 'digits', most significant first and padded with leading zeros. Returns false if n
+// doesn't fit. Handy for going through lots of numbers without allocating for each one.
+pub fn fill_digits<T>(n: T, base: T, digits: &mut [T]) -> bool
+where
+    T: Copy + Integer,
+{
+    assert!(n >= T::zero());
+    assert!(base > T::one());
+
+    let mut rest = n;
+    for d in digits.iter_mut().rev() {
+        let (div, rem) = rest.div_rem(&base);
+        *d = rem;
+        rest = div;
+    }
+    rest == T::zero()
+}
+
+// Exactly 'len' digits, padded with leading zeros, or None if n needs more than that
+pub fn to_digits_padded<T>(n: T, base: T, len: usize) -> Option<Vec<T>>
+where
+    T: Copy + Integer,
+{
+    let mut digits = vec![T::zero(); len];
+    if fill_digits(n, base, &mut digits) == true {
+        Some(digits)
+    } else {
+        None
+    }
+}
+
+pub fn from_digits<T>(digits: &[T], base: T) -> T
+where
+    T: Copy + Integer,
+{
+    digits.iter().fold(T::zero(), |n, d| {
+        assert!(*d >= T::zero() && *d < base, "Digit out of range");
+        n * base + *d
+    })
+}
+
+pub fn has_zero_digit<T>(n: T, base: T) -> bool
+where
+    T: Copy + Integer,
+{
+    to_digits(n, base).contains(&T::zero())
+}
+
+// Every 'len' digit combination where each digit is in 'allowed', in ascending or descending order. Like an odometer
+// whose wheels skip the digits that aren't allowed.
+pub struct ConstrainedDigits<T> {
+    allowed: RangeInclusive<T>,
+    next: Option<Vec<T>>,
+    descending: bool,
+}
+
+impl<T> ConstrainedDigits<T>
+where
+    T: Copy + Integer,
+{
+    pub fn ascending(len: usize, allowed: RangeInclusive<T>) -> Self {
+        Self::new(len, allowed, false)
+    }
+
+    pub fn descending(len: usize, allowed: RangeInclusive<T>) -> Self {
+        Self::new(len, allowed, true)
+    }
+
+    fn new(len: usize, allowed: RangeInclusive<T>, descending: bool) -> Self {
+        let first = if descending == true {
+            *allowed.end()
+        } else {
+            *allowed.start()
+        };
+        let next = if allowed.is_empty() == true {
+            None
+        } else {
+            Some(vec![first; len])
+        };
+        Self {
+            allowed,
+            next,
+            descending,
+        }
+    }
+}
+
+impl<T> Iterator for ConstrainedDigits<T>
+where
+    T: Copy + Integer,
+{
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.take()?;
+
+        let (from, to) = if self.descending == true {
+            (*self.allowed.start(), *self.allowed.end())
+        } else {
+            (*self.allowed.end(), *self.allowed.start())
+        };
+        let mut next = current.clone();
+        for d in next.iter_mut().rev() {
+            if *d != from {
+                *d = if self.descending == true {
+                    *d - T::one()
+                } else {
+                    *d + T::one()
+                };
+                self.next = Some(next);
+                return Some(current);
+            }
+            *d = to; // Roll over and carry into the next wheel
+        }
+
+        // Every wheel rolled over, so that was the last one
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_digits() {
+        assert_eq!(to_digits(0, 10), [0]);
+        assert_eq!(to_digits(13579, 10), [1, 3, 5, 7, 9]);
+        assert_eq!(to_digits(0b10110u32, 2), [1, 0, 1, 1, 0]);
+        assert_eq!(to_digits(0xbeefu64, 16), [11, 14, 14, 15]);
+        assert_eq!(from_digits(&[1, 3, 5, 7, 9], 10), 13579);
+        assert_eq!(from_digits(&[11, 14, 14, 15], 16), 0xbeef);
+        assert_eq!(from_digits::<i64>(&[], 10), 0);
+        for n in 0..1000i64 {
+            for base in [2, 3, 7, 10] {
+                assert_eq!(from_digits(&to_digits(n, base), base), n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_padded() {
+        assert_eq!(to_digits_padded(42, 10, 5), Some(vec![0, 0, 0, 4, 2]));
+        assert_eq!(to_digits_padded(42, 10, 2), Some(vec![4, 2]));
+        assert_eq!(to_digits_padded(42, 10, 1), None);
+        assert_eq!(to_digits_padded(5, 2, 4), Some(vec![0, 1, 0, 1]));
+        assert_eq!(to_digits_padded(0, 10, 0), Some(vec![]));
+
+        let mut digits = [0i64; 14];
+        assert_eq!(fill_digits(99394899891971, 10, &mut digits), true);
+        assert_eq!(digits, [9, 9, 3, 9, 4, 8, 9, 9, 8, 9, 1, 9, 7, 1]);
+        assert_eq!(fill_digits(999999999999999, 10, &mut digits), false);
+    }
+
+    #[test]
+    fn test_has_zero_digit() {
+        assert_eq!(has_zero_digit(0, 10), true);
+        assert_eq!(has_zero_digit(13579, 10), false);
+        assert_eq!(has_zero_digit(13509, 10), true);
+        assert_eq!(has_zero_digit(0b111, 2), false);
+        assert_eq!(has_zero_digit(0b101, 2), true);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_to_digits_fail_base() {
+        to_digits(3, 1);
+    }
+
+    #[test]
+    fn test_constrained_digits() {
+        let all: Vec<Vec<u8>> = ConstrainedDigits::ascending(2, 1..=3).collect();
+        assert_eq!(
+            all,
+            [
+                [1, 1],
+                [1, 2],
+                [1, 3],
+                [2, 1],
+                [2, 2],
+                [2, 3],
+                [3, 1],
+                [3, 2],
+                [3, 3]
+            ]
+        );
+
+        let mut descending = ConstrainedDigits::descending(14, 1..=9i64);
+        assert_eq!(from_digits(&descending.next().unwrap(), 10), 99999999999999);
+        assert_eq!(from_digits(&descending.next().unwrap(), 10), 99999999999998);
+        assert_eq!(from_digits(&descending.nth(7).unwrap(), 10), 99999999999989);
+
+        // Exactly the numbers without a zero, in order
+        let numbers: Vec<u32> = ConstrainedDigits::ascending(3, 1..=9)
+            .map(|d| from_digits(&d, 10))
+            .collect();
+        let expected: Vec<u32> = (100..1000)
+            .filter(|n| has_zero_digit(*n, 10) == false)
+            .collect();
+        assert_eq!(numbers, expected);
+
+        assert_eq!(ConstrainedDigits::ascending(3, 5..=5).count(), 1);
+        assert_eq!(ConstrainedDigits::ascending(0, 1..=9).count(), 1);
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = ConstrainedDigits::ascending(3, 9..=1);
+        assert_eq!(empty.count(), 0);
+    }
+}
@@ -1,21 +1,28 @@
+mod automaton;
 mod cardinal;
+mod digits;
 mod linked_list_circ;
 mod mode;
 mod modulus;
 mod parser;
 mod point2;
 mod point3;
+mod random;
 mod range2;
 mod range3;
+mod rotation3;
 mod tile;
 
+pub use automaton::*;
 pub use cardinal::*;
-pub use linked_list_circ::*;
+pub use digits::*;
 pub use mode::*;
 pub use modulus::*;
 pub use parser::*;
 pub use point2::*;
 pub use point3::*;
+pub use random::*;
 pub use range2::*;
 pub use range3::*;
+pub use rotation3::*;
 pub use tile::*;
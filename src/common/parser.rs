@@ -6,6 +6,7 @@ use nom::{
     sequence::{delimited, pair, preceded, terminated},
     IResult, Parser,
 };
+use num::Num;
 use std::str::FromStr;
 
 pub fn unsigned<T: FromStr>(input: &str) -> IResult<&str, T> {
@@ -18,10 +19,9 @@ pub fn signed<T: FromStr>(input: &str) -> IResult<&str, T> {
     })(input)
 }
 
-pub fn binary(input: &str) -> IResult<&str, u32> {
-    // TODO: make this generic on any primitive integer rather than just u32
+pub fn binary<T: Num>(input: &str) -> IResult<&str, T> {
     map_res(recognize(many1(one_of("01"))), |x: &str| {
-        u32::from_str_radix(x, 2)
+        T::from_str_radix(x, 2)
     })(input)
 }
 
@@ -145,29 +145,39 @@ mod test {
     #[test]
     fn test_binary() {
         let input = "101";
-        let (remain, num) = binary(input).unwrap();
+        let (remain, num) = binary::<u32>(input).unwrap();
         assert_eq!(remain, "");
         assert_eq!(num, 5);
 
         let input = "11111111111111111111111111111111";
-        let (remain, num) = binary(input).unwrap();
+        let (remain, num) = binary::<u32>(input).unwrap();
         assert_eq!(remain, "");
         assert_eq!(num, 0xFFFFFFFF);
 
         let input = "101abc";
-        let (remain, num) = binary(input).unwrap();
+        let (remain, num) = binary::<u32>(input).unwrap();
         assert_eq!(remain, "abc");
         assert_eq!(num, 5);
 
         let input = "0001abc";
-        let (remain, num) = binary(input).unwrap();
+        let (remain, num) = binary::<u32>(input).unwrap();
         assert_eq!(remain, "abc");
         assert_eq!(num, 1);
 
         let input = "01234";
-        let (remain, num) = binary(input).unwrap();
+        let (remain, num) = binary::<u32>(input).unwrap();
         assert_eq!(remain, "234");
         assert_eq!(num, 1);
+
+        let input = "1".repeat(100);
+        let (remain, num) = binary::<u128>(&input).unwrap();
+        assert_eq!(remain, "");
+        assert_eq!(num, (1 << 100) - 1);
+
+        // Too wide for the type
+        let input = "1".repeat(33);
+        assert!(binary::<u32>(&input).is_err());
+        assert_eq!(binary::<u64>(&input).unwrap().1, 0x1_FFFF_FFFF);
     }
 
     #[test]
@@ -0,0 +1,53 @@
+use crate::common::{Point3, Range3};
+
+// Small splitmix64 generator for reproducible test data without pulling in another crate
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        // Inclusive of both ends
+        let span = (high - low) as u64 + 1;
+        low + (self.next_u64() % span) as i32
+    }
+
+    pub fn point_in(&mut self, range: &Range3) -> Point3 {
+        Point3 {
+            x: self.range(range.x.0, range.x.1),
+            y: self.range(range.y.0, range.y.1),
+            z: self.range(range.z.0, range.z.1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_mix() {
+        let mut a = SplitMix64::new(7);
+        let mut b = SplitMix64::new(7);
+        let first: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        let second: Vec<u64> = (0..10).map(|_| b.next_u64()).collect();
+        assert_eq!(first, second);
+        assert_ne!(SplitMix64::new(8).next_u64(), first[0]);
+
+        let mut rng = SplitMix64::new(1);
+        let values: Vec<i32> = (0..1000).map(|_| rng.range(-2, 2)).collect();
+        assert!(values.iter().all(|v| (-2..=2).contains(v)));
+        assert!(values.contains(&-2) && values.contains(&2));
+        assert!((0..10).all(|_| rng.range(5, 5) == 5));
+    }
+}
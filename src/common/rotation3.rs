@@ -0,0 +1,173 @@
+use crate::common::Point3;
+use auto_ops::*;
+
+// One of the 24 rotations that map the axes onto each other, stored as a row-major integer matrix.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Rotation3 {
+    m: [[i32; 3]; 3],
+}
+
+impl Rotation3 {
+    pub const IDENTITY: Self = Self {
+        m: [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+    };
+
+    const QUARTER_X: Self = Self {
+        m: [[1, 0, 0], [0, 0, -1], [0, 1, 0]],
+    };
+
+    const QUARTER_Y: Self = Self {
+        m: [[0, 0, 1], [0, 1, 0], [-1, 0, 0]],
+    };
+
+    const QUARTER_Z: Self = Self {
+        m: [[0, -1, 0], [1, 0, 0], [0, 0, 1]],
+    };
+
+    pub fn all() -> impl Iterator<Item = Self> {
+        // Every signed permutation matrix is a rotation or a reflection; keep only the rotations
+        const PERMUTATIONS: [[usize; 3]; 6] = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+        PERMUTATIONS
+            .into_iter()
+            .flat_map(|perm| {
+                (0..8).map(move |signs| {
+                    let mut m = [[0; 3]; 3];
+                    for (row, col) in perm.into_iter().enumerate() {
+                        m[row][col] = if signs & (1 << row) == 0 { 1 } else { -1 };
+                    }
+                    Self { m }
+                })
+            })
+            .filter(|r| r.determinant() == 1)
+    }
+
+    // Right-handed quarter turns around each axis, e.g. a single turn around x takes y to z
+    pub fn about_x(quarter_turns: u32) -> Self {
+        Self::QUARTER_X.pow(quarter_turns)
+    }
+
+    pub fn about_y(quarter_turns: u32) -> Self {
+        Self::QUARTER_Y.pow(quarter_turns)
+    }
+
+    pub fn about_z(quarter_turns: u32) -> Self {
+        Self::QUARTER_Z.pow(quarter_turns)
+    }
+
+    fn pow(self, n: u32) -> Self {
+        (0..n % 4).fold(Self::IDENTITY, |acc, _| self * acc)
+    }
+
+    pub fn matrix(&self) -> [[i32; 3]; 3] {
+        self.m
+    }
+
+    pub fn apply(&self, p: Point3) -> Point3 {
+        let row = |r: [i32; 3]| r[0] * p.x + r[1] * p.y + r[2] * p.z;
+        Point3 {
+            x: row(self.m[0]),
+            y: row(self.m[1]),
+            z: row(self.m[2]),
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        // Rotation matrices are orthogonal so the inverse is just the transpose
+        let mut m = [[0; 3]; 3];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = self.m[c][r];
+            }
+        }
+        Self { m }
+    }
+
+    fn determinant(&self) -> i32 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
+
+impl Default for Rotation3 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+// Composition: (a * b) rotates by b first and then by a
+impl_op_ex!(*|a: &Rotation3, b: &Rotation3| -> Rotation3 {
+    let mut m = [[0; 3]; 3];
+    for (r, row) in m.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|i| a.m[r][i] * b.m[i][c]).sum();
+        }
+    }
+    Rotation3 { m }
+});
+
+impl_op_ex!(*|a: &Rotation3, b: &Point3| -> Point3 { a.apply(*b) });
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_all() {
+        let all: Vec<Rotation3> = Rotation3::all().collect();
+        assert_eq!(all.len(), 24);
+        assert_eq!(all[0], Rotation3::IDENTITY);
+
+        let unique: HashSet<Rotation3> = all.iter().copied().collect();
+        assert_eq!(unique.len(), 24);
+
+        // The rotations form a group: closed under composition and inverse
+        for a in &all {
+            assert!(unique.contains(&a.inverse()));
+            for b in &all {
+                assert!(unique.contains(&(a * b)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_about_axis() {
+        let p = Point3 { x: 1, y: 2, z: 3 };
+        assert_eq!(Rotation3::about_x(1).apply(p), (1, -3, 2).into());
+        assert_eq!(Rotation3::about_y(1).apply(p), (3, 2, -1).into());
+        assert_eq!(Rotation3::about_z(1).apply(p), (-2, 1, 3).into());
+        assert_eq!(Rotation3::about_z(2).apply(p), (-1, -2, 3).into());
+        assert_eq!(Rotation3::about_x(4), Rotation3::IDENTITY);
+        assert_eq!(Rotation3::about_y(0), Rotation3::IDENTITY);
+    }
+
+    #[test]
+    fn test_compose() {
+        let p = Point3 { x: 1, y: 2, z: 3 };
+        let a = Rotation3::about_x(1);
+        let b = Rotation3::about_z(3);
+        assert_eq!((a * b).apply(p), a.apply(b.apply(p)));
+        assert_eq!((b * a).apply(p), b.apply(a.apply(p)));
+        assert_ne!(a * b, b * a);
+        assert_eq!(a * b * p, a.apply(b.apply(p)));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let p = Point3 { x: 1, y: 2, z: 3 };
+        for r in Rotation3::all() {
+            assert_eq!(r * r.inverse(), Rotation3::IDENTITY);
+            assert_eq!(r.inverse().apply(r.apply(p)), p);
+        }
+        assert_eq!(Rotation3::about_y(1).inverse(), Rotation3::about_y(3));
+    }
+}
@@ -76,15 +76,164 @@
     Consider sums of a three-measurement sliding window. How many sums are larger than the previous sum?
 */
 
-use itertools::Itertools;
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+// Everything about the sums of one window size, updated one reading at a time. Only the readings in the current
+// window are kept, so memory doesn't depend on how long the log is.
+#[derive(Clone, Debug)]
+pub struct WindowStats {
+    size: usize,
+    window: VecDeque<u32>,
+    sum: u64,
+    last_sum: Option<u64>,
+    windows: usize,
+    increases: usize,
+    run: usize,
+    longest_run: usize,
+    min_sum: Option<u64>,
+    max_sum: Option<u64>,
+}
+
+impl WindowStats {
+    fn new(size: usize) -> Self {
+        assert!(size > 0, "Windows need at least one measurement");
+        Self {
+            size,
+            window: VecDeque::with_capacity(size + 1),
+            sum: 0,
+            last_sum: None,
+            windows: 0,
+            increases: 0,
+            run: 0,
+            longest_run: 0,
+            min_sum: None,
+            max_sum: None,
+        }
+    }
+
+    fn push(&mut self, reading: u32) -> Option<u64> {
+        self.window.push_back(reading);
+        self.sum += reading as u64;
+        if self.window.len() > self.size {
+            self.sum -= self.window.pop_front().unwrap() as u64;
+        }
+        if self.window.len() < self.size {
+            return None;
+        }
+
+        let sum = self.sum;
+        self.windows += 1;
+        if matches!(self.last_sum, Some(last) if sum > last) {
+            self.increases += 1;
+            self.run += 1;
+        } else {
+            self.run = 1;
+        }
+        self.longest_run = self.longest_run.max(self.run);
+        self.min_sum = Some(self.min_sum.map_or(sum, |m| m.min(sum)));
+        self.max_sum = Some(self.max_sum.map_or(sum, |m| m.max(sum)));
+        self.last_sum = Some(sum);
+        Some(sum)
+    }
+
+    // How many sums are larger than the one before
+    pub fn increases(&self) -> usize {
+        self.increases
+    }
+
+    // The most sums in a row that each got larger
+    pub fn longest_increasing_run(&self) -> usize {
+        self.longest_run
+    }
+
+    pub fn windows(&self) -> usize {
+        self.windows
+    }
+
+    pub fn min_sum(&self) -> Option<u64> {
+        self.min_sum
+    }
+
+    pub fn max_sum(&self) -> Option<u64> {
+        self.max_sum
+    }
+}
+
+// Any number of window sizes fed from the same stream of readings
+#[derive(Clone, Debug)]
+pub struct SonarSweep {
+    readings: usize,
+    stats: Vec<WindowStats>,
+}
+
+impl SonarSweep {
+    pub fn new(window_sizes: &[usize]) -> Self {
+        Self {
+            readings: 0,
+            stats: window_sizes
+                .iter()
+                .map(|size| WindowStats::new(*size))
+                .collect(),
+        }
+    }
+
+    // Reads one measurement per line. Bad lines stop the sweep with an error naming the line.
+    pub fn from_reader<R: BufRead>(reader: R, window_sizes: &[usize]) -> std::io::Result<Self> {
+        let mut sweep = Self::new(window_sizes);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() == true {
+                continue;
+            }
+            let reading = line.trim().parse::<u32>().map_err(|e| {
+                let msg = format!("Line {}: \"{}\" isn't a measurement ({})", i + 1, line, e);
+                std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+            })?;
+            sweep.push(reading);
+        }
+        Ok(sweep)
+    }
+
+    pub fn push(&mut self, reading: u32) {
+        self.readings += 1;
+        for stats in &mut self.stats {
+            stats.push(reading);
+        }
+    }
+
+    pub fn readings(&self) -> usize {
+        self.readings
+    }
+
+    pub fn stats(&self, window_size: usize) -> Option<&WindowStats> {
+        self.stats.iter().find(|s| s.size == window_size)
+    }
+}
+
+impl Extend<u32> for SonarSweep {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, readings: I) {
+        for reading in readings {
+            self.push(reading);
+        }
+    }
+}
+
+// The sum of every window, as the readings come in
+pub fn window_sums<I>(readings: I, window_size: usize) -> impl Iterator<Item = u64>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut stats = WindowStats::new(window_size);
+    readings
+        .into_iter()
+        .filter_map(move |reading| stats.push(reading))
+}
 
 fn count_larger_measurements(measurements: &[u32], window_size: usize) -> usize {
-    measurements
-        .windows(window_size) // The number of measurements that constitute a sum
-        .map(|x| x.iter().sum::<u32>())
-        .tuple_windows() // Compare every pair of sums
-        .filter(|(a, b)| b > a)
-        .count()
+    let mut sweep = SonarSweep::new(&[window_size]);
+    sweep.extend(measurements.iter().copied());
+    sweep.stats(window_size).unwrap().increases()
 }
 
 #[aoc_generator(day1)]
@@ -139,4 +288,64 @@ mod test {
         let count = count_larger_measurements(&input, 3);
         assert_eq!(count, 5);
     }
+
+    #[test]
+    fn test_sweep() {
+        let sweep = SonarSweep::from_reader(EXAMPLE_INPUT.as_bytes(), &[1, 3, 20]).unwrap();
+        assert_eq!(sweep.readings(), 10);
+
+        let single = sweep.stats(1).unwrap();
+        assert_eq!(single.increases(), 7);
+        assert_eq!(single.windows(), 10);
+        assert_eq!(single.longest_increasing_run(), 4); // 199, 200, 208, 210
+        assert_eq!(single.min_sum(), Some(199));
+        assert_eq!(single.max_sum(), Some(269));
+
+        let triple = sweep.stats(3).unwrap();
+        assert_eq!(triple.increases(), 5);
+        assert_eq!(triple.windows(), 8);
+        assert_eq!(triple.longest_increasing_run(), 5); // 555, 560, 618, 647, 716
+        assert_eq!(triple.min_sum(), Some(607));
+        assert_eq!(triple.max_sum(), Some(792));
+
+        // Longer than the log, so there are no windows at all
+        let long = sweep.stats(20).unwrap();
+        assert_eq!(long.windows(), 0);
+        assert_eq!(long.longest_increasing_run(), 0);
+        assert_eq!(long.min_sum(), None);
+
+        assert!(sweep.stats(2).is_none());
+    }
+
+    #[test]
+    fn test_window_sums() {
+        let input = input_generator(EXAMPLE_INPUT);
+        let sums: Vec<u64> = window_sums(input.iter().copied(), 3).collect();
+        assert_eq!(sums, [607, 618, 618, 617, 647, 716, 769, 792]);
+
+        // A long log never has to be held in memory
+        let readings = (0..1_000_000u32).map(|i| i.wrapping_mul(7919) % 1000);
+        let mut sweep = SonarSweep::new(&[1, 3, 50]);
+        sweep.extend(readings.clone());
+        let all: Vec<u32> = readings.collect();
+        for size in [1, 3, 50] {
+            let sums: Vec<u64> = all
+                .windows(size)
+                .map(|w| w.iter().map(|x| *x as u64).sum())
+                .collect();
+            let increases = sums.windows(2).filter(|p| p[1] > p[0]).count();
+            assert_eq!(sweep.stats(size).unwrap().increases(), increases);
+            assert_eq!(
+                sweep.stats(size).unwrap().max_sum(),
+                sums.iter().max().copied()
+            );
+        }
+    }
+
+    #[test]
+    fn test_sweep_bad_line() {
+        let err = SonarSweep::from_reader("199\n200\nabc\n".as_bytes(), &[1]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("Line 3: \"abc\""));
+    }
 }
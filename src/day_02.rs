@@ -54,14 +54,17 @@
     Using this new interpretation of the commands, calculate the horizontal position and depth you would have after following the planned course. What do you get if you multiply your final horizontal position by your final depth?
 */
 
-use crate::common::{trim_start, unsigned, Point2};
+use crate::common::{unsigned, Point2};
 use nom::{
-    character::complete::{alpha1, char},
-    multi::many1,
-    sequence::separated_pair,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::space0,
+    combinator::{all_consuming, map},
+    sequence::{delimited, preceded},
     IResult,
 };
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Command {
     Forward(u32),
     Down(u32),
@@ -70,69 +73,151 @@ pub enum Command {
 
 impl Command {
     fn parser(input: &str) -> IResult<&str, Self> {
-        let (input, (cmd, arg)) = separated_pair(trim_start(alpha1), char(' '), unsigned)(input)?;
+        alt((
+            map(preceded(tag("forward "), unsigned), Self::Forward),
+            map(preceded(tag("down "), unsigned), Self::Down),
+            map(preceded(tag("up "), unsigned), Self::Up),
+        ))(input)
+    }
 
-        let command = match cmd {
-            "forward" => Self::Forward(arg),
-            "down" => Self::Down(arg),
-            "up" => Self::Up(arg),
-            _ => panic!("Unknown command"),
-        };
+    fn from_line(line: &str) -> Option<Self> {
+        let (_, command) = all_consuming(delimited(space0, Self::parser, space0))(line).ok()?;
+        Some(command)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    line: usize, // 1-based
+    text: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: can't understand \"{}\"", self.line, self.text)
+    }
+}
+
+fn parse_commands(input: &str) -> Result<Vec<Command>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim().is_empty() == false)
+        .map(|(i, line)| {
+            Command::from_line(line).ok_or_else(|| ParseError {
+                line: i + 1,
+                text: line.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct State {
+    position: i32,
+    depth: i32,
+    aim: i32,
+}
+
+// What a command actually does to the submarine
+pub trait Movement {
+    fn apply(&self, state: State, command: &Command) -> State;
+}
+
+// Part 1: up and down change the depth directly
+pub struct Direct;
+
+// Part 2: up and down only change the aim, and moving forward follows it
+pub struct Aimed;
+
+impl Movement for Direct {
+    fn apply(&self, state: State, command: &Command) -> State {
+        match command {
+            Command::Forward(d) => State {
+                position: state.position + *d as i32,
+                ..state
+            },
+            Command::Down(d) => State {
+                depth: state.depth + *d as i32,
+                ..state
+            },
+            Command::Up(d) => State {
+                depth: state.depth - *d as i32,
+                ..state
+            },
+        }
+    }
+}
 
-        Ok((input, command))
+impl Movement for Aimed {
+    fn apply(&self, state: State, command: &Command) -> State {
+        match command {
+            Command::Forward(d) => State {
+                position: state.position + *d as i32,
+                depth: state.depth + state.aim * *d as i32,
+                ..state
+            },
+            Command::Down(d) => State {
+                aim: state.aim + *d as i32,
+                ..state
+            },
+            Command::Up(d) => State {
+                aim: state.aim - *d as i32,
+                ..state
+            },
+        }
     }
 }
 
 struct Submarine {
-    pos: Point2,
-    aim: u32,
+    state: State,
 }
 
 impl Submarine {
     fn new() -> Self {
         Self {
-            pos: Point2::origin(),
-            aim: 0,
+            state: State::default(),
         }
     }
 
-    fn follow_commands1(&mut self, commands: &[Command]) {
+    fn pos(&self) -> Point2 {
+        Point2 {
+            x: self.state.position,
+            y: self.state.depth,
+        }
+    }
+
+    fn follow<M: Movement>(&mut self, movement: &M, commands: &[Command]) {
         for cmd in commands {
-            match cmd {
-                Command::Forward(d) => self.pos.x += *d as i32,
-                Command::Down(d) => self.pos.y += *d as i32,
-                Command::Up(d) => self.pos.y -= *d as i32,
-            }
+            self.state = movement.apply(self.state, cmd);
         }
     }
 
-    fn follow_commands2(&mut self, commands: &[Command]) {
+    // Every state along the way, starting with the current one
+    fn trajectory<M: Movement>(&mut self, movement: &M, commands: &[Command]) -> Vec<State> {
+        let mut states = Vec::with_capacity(commands.len() + 1);
+        states.push(self.state);
         for cmd in commands {
-            match cmd {
-                Command::Forward(d) => {
-                    self.pos.x += *d as i32;
-                    self.pos.y += (self.aim * d) as i32;
-                }
-                Command::Down(d) => self.aim += *d,
-                Command::Up(d) => self.aim -= *d,
-            }
+            self.state = movement.apply(self.state, cmd);
+            states.push(self.state);
         }
+        states
     }
 
     fn calc_pos_score(&self) -> i32 {
-        self.pos.x * self.pos.y
+        self.state.position * self.state.depth
     }
 }
 
 #[aoc_generator(day2)]
 pub fn input_generator(input: &str) -> Vec<Command> {
-    many1(Command::parser)(input).unwrap().1
+    parse_commands(input).unwrap_or_else(|e| panic!("{}", e))
 }
 
 #[aoc(day2, part1)]
 pub fn part1(input: &[Command]) -> i32 {
     let mut sub = Submarine::new();
-    sub.follow_commands1(input);
+    sub.follow(&Direct, input);
     let score = sub.calc_pos_score();
     assert_eq!(score, 1840243);
     score
@@ -141,7 +226,7 @@ pub fn part1(input: &[Command]) -> i32 {
 #[aoc(day2, part2)]
 pub fn part2(input: &[Command]) -> i32 {
     let mut sub = Submarine::new();
-    sub.follow_commands2(input);
+    sub.follow(&Aimed, input);
     let score = sub.calc_pos_score();
     assert_eq!(score, 1727785422);
     score
@@ -164,8 +249,8 @@ forward 2
     fn test_follow_commands1() {
         let mut sub = Submarine::new();
         let input = input_generator(EXAMPLE_INPUT);
-        sub.follow_commands1(&input);
-        assert_eq!(sub.pos, Point2 { x: 15, y: 10 });
+        sub.follow(&Direct, &input);
+        assert_eq!(sub.pos(), Point2 { x: 15, y: 10 });
 
         let score = sub.calc_pos_score();
         assert_eq!(score, 150);
@@ -175,10 +260,81 @@ forward 2
     fn test_follow_commands2() {
         let mut sub = Submarine::new();
         let input = input_generator(EXAMPLE_INPUT);
-        sub.follow_commands2(&input);
-        assert_eq!(sub.pos, Point2 { x: 15, y: 60 });
+        sub.follow(&Aimed, &input);
+        assert_eq!(sub.pos(), Point2 { x: 15, y: 60 });
 
         let score = sub.calc_pos_score();
         assert_eq!(score, 900);
     }
+
+    #[test]
+    fn test_trajectory() {
+        let input = input_generator(EXAMPLE_INPUT);
+        let state = |position, depth, aim| State {
+            position,
+            depth,
+            aim,
+        };
+
+        let mut sub = Submarine::new();
+        let states = sub.trajectory(&Aimed, &input);
+        assert_eq!(
+            states,
+            [
+                state(0, 0, 0),
+                state(5, 0, 0),
+                state(5, 0, 5),
+                state(13, 40, 5),
+                state(13, 40, 2),
+                state(13, 40, 10),
+                state(15, 60, 10)
+            ]
+        );
+        assert_eq!(sub.calc_pos_score(), 900);
+
+        let mut sub = Submarine::new();
+        let states = sub.trajectory(&Direct, &input);
+        assert_eq!(states.len(), input.len() + 1);
+        assert_eq!(states[3], state(13, 5, 0));
+        assert_eq!(states.last(), Some(&state(15, 10, 0)));
+
+        // Another kind of movement, where the controls are upside down
+        struct Inverted;
+        impl Movement for Inverted {
+            fn apply(&self, state: State, command: &Command) -> State {
+                let flipped = match command {
+                    Command::Down(d) => Command::Up(*d),
+                    Command::Up(d) => Command::Down(*d),
+                    other => *other,
+                };
+                Direct.apply(state, &flipped)
+            }
+        }
+        let mut sub = Submarine::new();
+        sub.follow(&Inverted, &input);
+        assert_eq!(sub.pos(), Point2 { x: 15, y: -10 });
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_commands(EXAMPLE_INPUT).unwrap().len(), 6);
+        assert_eq!(
+            parse_commands("  up 3  \n\ndown 4").unwrap(),
+            [Command::Up(3), Command::Down(4)]
+        );
+
+        let err = parse_commands("forward 5\nsideways 2\nup 1\n").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                line: 2,
+                text: "sideways 2".to_string()
+            }
+        );
+        assert_eq!(err.to_string(), "Line 2: can't understand \"sideways 2\"");
+
+        for bad in ["forward", "down -3", "up 3 4", "forward five"] {
+            assert_eq!(parse_commands(bad).unwrap_err().line, 1);
+        }
+    }
 }
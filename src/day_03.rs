@@ -66,144 +66,220 @@
 use crate::common::binary;
 use nom::{
     character::complete::{multispace0, one_of},
+    combinator::consumed,
+    error::{Error, ErrorKind},
     multi::{many1, many1_count},
     sequence::preceded,
     IResult,
 };
+use num::PrimInt;
 use std::cmp::Ordering;
 
-pub struct Diagnostic {
-    numbers: Vec<u32>,
+// What to do with a bit position that has as many ones as zeroes. The puzzle never says for gamma and epsilon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TiePolicy {
+    PreferOne,
+    PreferZero,
+    Reject,
+}
+
+pub struct Diagnostic<T> {
+    numbers: Vec<T>,
     valid_bits: u32,
+    trie: BitTrie,
 }
 
-impl Diagnostic {
+impl<T> Diagnostic<T>
+where
+    T: PrimInt,
+{
     fn parser(input: &str) -> IResult<&str, Self> {
-        let count_bits: IResult<&str, usize> = many1_count(one_of("01"))(input);
-        let valid_bits = count_bits.unwrap().1 as u32;
-        let mut numbers = many1(preceded(multispace0, binary))(input).unwrap().1;
-        numbers.sort_unstable();
+        let (_, valid_bits) = preceded(multispace0, many1_count(one_of("01")))(input)?;
+        let valid_bits = valid_bits as u32;
+        if valid_bits > T::zero().count_zeros() {
+            // Leading zeros would parse fine, but there'd be no room for the bits
+            return Err(nom::Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+        }
+        let (input, lines) = many1(preceded(multispace0, consumed(binary)))(input)?;
+        let mut numbers = Vec::with_capacity(lines.len());
+        for (text, n) in lines {
+            if text.len() != valid_bits as usize {
+                // Every line has to be the same width, or the bit positions don't line up
+                return Err(nom::Err::Failure(Error::new(text, ErrorKind::LengthValue)));
+            }
+            numbers.push(n);
+        }
+        let trie = BitTrie::new(&numbers, valid_bits);
 
         Ok((
             input,
             Diagnostic {
                 numbers,
                 valid_bits,
+                trie,
             },
         ))
     }
 
-    fn gamma_epsilon_rate(&self) -> (u32, u32) {
-        let mut gamma = 0;
-        let mut epsilon = 0;
-        for bit in 0..self.valid_bits {
-            let (ones, zeroes) = count_values_at_bit(&self.numbers, bit);
-            match ones.cmp(&zeroes) {
-                Ordering::Greater => gamma |= 1 << bit,
-                Ordering::Less => epsilon |= 1 << bit,
-                Ordering::Equal => panic!("Equal ones and zeroes in gamma epsilon"), // Undefined what to do
+    // On a reject, the error is the lowest tied bit
+    fn gamma_epsilon_rate(&self, tie: TiePolicy) -> Result<(T, T), u32> {
+        // Tally every set bit in a single pass over the report
+        let mut ones = vec![0usize; self.valid_bits as usize];
+        for n in &self.numbers {
+            let mut rest = *n;
+            while rest != T::zero() {
+                ones[rest.trailing_zeros() as usize] += 1;
+                rest = rest & (rest - T::one());
             }
         }
-        (gamma, epsilon)
+
+        let mut gamma = T::zero();
+        let mut epsilon = T::zero();
+        for (bit, ones) in ones.into_iter().enumerate() {
+            let zeroes = self.numbers.len() - ones;
+            let most_common_is_one = match ones.cmp(&zeroes) {
+                Ordering::Greater => true,
+                Ordering::Less => false,
+                Ordering::Equal => match tie {
+                    TiePolicy::PreferOne => true,
+                    TiePolicy::PreferZero => false,
+                    TiePolicy::Reject => return Err(bit as u32),
+                },
+            };
+            if most_common_is_one == true {
+                gamma = gamma | T::one() << bit;
+            } else {
+                epsilon = epsilon | T::one() << bit;
+            }
+        }
+        Ok((gamma, epsilon))
     }
 
-    fn power_consumption(&self) -> u32 {
-        let (gamma, epsilon) = self.gamma_epsilon_rate();
-        gamma * epsilon
+    fn power_consumption(&self, tie: TiePolicy) -> Result<T, u32> {
+        let (gamma, epsilon) = self.gamma_epsilon_rate(tie)?;
+        Ok(gamma * epsilon)
     }
 
-    fn oxygen_bit_criteria(ones: u32, zeroes: u32) -> bool {
+    fn oxygen_bit_criteria(ones: usize, zeroes: usize) -> bool {
         ones >= zeroes
     }
 
-    fn co2_bit_criteria(ones: u32, zeroes: u32) -> bool {
+    fn co2_bit_criteria(ones: usize, zeroes: usize) -> bool {
         ones < zeroes
     }
 
-    fn calc_rating(&self, bit_criteria_fn: fn(u32, u32) -> bool) -> u32 {
-        // With the input data sorted, this puzzle becomes about splitting the list at the next bit,
-        // and keeping only the portion lower or higher than that point. Rather than searching the list
-        // every single iteration, we only need to examine the portion that falls within the range
-        // we care about.
-        let mut low_idx = 0;
-        let mut hi_idx = self.numbers.len() - 1;
-        let mut prefix = 0usize;
-        for bit in (0..self.valid_bits).rev() {
-            // Find the prefix's location in the vector, or the first location greater than its value
-            let prefix_split = prefix | (1 << bit);
-            let mid_idx = (&self.numbers[low_idx..=hi_idx])
-                .iter()
-                .position(|x| *x >= prefix_split as u32)
-                .unwrap()
-                + low_idx;
-
-            // Zeroes are from low to mid (exclusive), ones are from mid (inclusive) to high
-            let ones = hi_idx - mid_idx + 1;
-            let zeroes = mid_idx - low_idx;
-            // Note: the case where there are no ones would appear as a panic on unwrap when finding mid_idx above.
-            // The case where there are no zeroes should work unless the puzzle is unsolvable (i.e. CO2 rating where the
-            // less common value has 0 appearances).
-
-            // Check against bit criteria
-            if bit_criteria_fn(ones as u32, zeroes as u32) == true {
-                // Keep upper portion
-                low_idx = mid_idx;
-                prefix |= 1 << bit;
-            } else {
-                // Keep lower portion
-                hi_idx = mid_idx - 1;
-                prefix &= !(1 << bit);
-            }
-
-            if low_idx == hi_idx {
-                return self.numbers[low_idx];
-            }
-        }
-        panic!("Did not find match");
+    // The bit criteria gets the count of ones and zeroes among the numbers still in the running, and returns whether
+    // to keep the ones
+    fn calc_rating<F>(&self, bit_criteria: F) -> Option<T>
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        self.trie.find(bit_criteria)
     }
 
-    fn oxygen_rating(&self) -> u32 {
-        self.calc_rating(Diagnostic::oxygen_bit_criteria)
+    fn oxygen_rating(&self) -> T {
+        self.calc_rating(Self::oxygen_bit_criteria)
+            .expect("Did not find match")
     }
 
-    fn co2_rating(&self) -> u32 {
-        self.calc_rating(Diagnostic::co2_bit_criteria)
+    fn co2_rating(&self) -> T {
+        self.calc_rating(Self::co2_bit_criteria)
+            .expect("Did not find match")
     }
 
-    fn life_support_rating(&self) -> u32 {
+    fn life_support_rating(&self) -> T {
         self.oxygen_rating() * self.co2_rating()
     }
 }
 
-fn count_values_at_bit(numbers: &[u32], bit: u32) -> (u32, u32) {
-    let mut ones = 0;
-    let mut zeroes = 0;
-    for n in numbers {
-        let bit = *n & (1 << bit);
-        if bit != 0 {
-            ones += 1;
-        } else {
-            zeroes += 1;
+#[derive(Clone, Copy, Debug, Default)]
+struct TrieNode {
+    count: usize,
+    children: [Option<usize>; 2],
+}
+
+// The report as a binary trie, most significant bit first. Every node knows how many numbers pass through it, so
+// filtering by bit criteria is a walk down from the root rather than a rescan of the list at each bit.
+struct BitTrie {
+    nodes: Vec<TrieNode>,
+    depth: u32,
+}
+
+impl BitTrie {
+    fn new<T: PrimInt>(numbers: &[T], depth: u32) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+        for n in numbers {
+            let mut node = 0;
+            nodes[node].count += 1;
+            for bit in (0..depth as usize).rev() {
+                let branch = ((*n >> bit) & T::one() == T::one()) as usize;
+                node = match nodes[node].children[branch] {
+                    Some(child) => child,
+                    None => {
+                        nodes.push(TrieNode::default());
+                        let child = nodes.len() - 1;
+                        nodes[node].children[branch] = Some(child);
+                        child
+                    }
+                };
+                nodes[node].count += 1;
+            }
         }
+        BitTrie { nodes, depth }
+    }
+
+    fn count(&self, node: Option<usize>) -> usize {
+        node.map_or(0, |n| self.nodes[n].count)
     }
 
-    (ones, zeroes)
+    // None if the report is empty or the criteria pick a bit that none of the remaining numbers have
+    fn find<T, F>(&self, bit_criteria: F) -> Option<T>
+    where
+        T: PrimInt,
+        F: Fn(usize, usize) -> bool,
+    {
+        if self.nodes[0].count == 0 {
+            return None;
+        }
+
+        let mut node = 0;
+        let mut value = T::zero();
+        for _ in 0..self.depth {
+            let [zero, one] = self.nodes[node].children;
+            let keep_ones = if self.nodes[node].count == 1 {
+                // Down to one number, so just follow it to the end
+                one.is_some()
+            } else {
+                bit_criteria(self.count(one), self.count(zero))
+            };
+            value = value << 1;
+            if keep_ones == true {
+                value = value | T::one();
+                node = one?;
+            } else {
+                node = zero?;
+            }
+        }
+        Some(value)
+    }
 }
 
 #[aoc_generator(day3)]
-pub fn input_generator(input: &str) -> Diagnostic {
+pub fn input_generator(input: &str) -> Diagnostic<u32> {
     Diagnostic::parser(input).unwrap().1
 }
 
 #[aoc(day3, part1)]
-pub fn part1(input: &Diagnostic) -> u32 {
-    let power = input.power_consumption();
+pub fn part1(input: &Diagnostic<u32>) -> u32 {
+    let power = input
+        .power_consumption(TiePolicy::Reject)
+        .unwrap_or_else(|bit| panic!("Equal ones and zeroes at bit {}", bit));
     assert_eq!(power, 738234);
     power
 }
 
 #[aoc(day3, part2)]
-pub fn part2(input: &Diagnostic) -> u32 {
+pub fn part2(input: &Diagnostic<u32>) -> u32 {
     let life = input.life_support_rating();
     assert_eq!(life, 3969126);
     life
@@ -231,7 +307,7 @@ mod test {
     #[test]
     fn test_gamma_epsilon_rate() {
         let input = input_generator(EXAMPLE_INPUT);
-        let (gamma, epsilon) = input.gamma_epsilon_rate();
+        let (gamma, epsilon) = input.gamma_epsilon_rate(TiePolicy::Reject).unwrap();
         assert_eq!(gamma, 22);
         assert_eq!(epsilon, 9);
     }
@@ -239,7 +315,7 @@ mod test {
     #[test]
     fn test_power_consumption() {
         let input = input_generator(EXAMPLE_INPUT);
-        let power = input.power_consumption();
+        let power = input.power_consumption(TiePolicy::Reject).unwrap();
         assert_eq!(power, 198);
     }
 
@@ -263,4 +339,72 @@ mod test {
         let life = input.life_support_rating();
         assert_eq!(life, 230);
     }
+
+    #[test]
+    fn test_ties() {
+        let input = input_generator("1101\n1010\n0111\n0001\n");
+        assert_eq!(input.gamma_epsilon_rate(TiePolicy::Reject), Err(1));
+        assert_eq!(
+            input.gamma_epsilon_rate(TiePolicy::PreferOne),
+            Ok((0b1111, 0b0000))
+        );
+        assert_eq!(
+            input.gamma_epsilon_rate(TiePolicy::PreferZero),
+            Ok((0b0001, 0b1110))
+        );
+        assert_eq!(input.power_consumption(TiePolicy::PreferZero), Ok(14));
+    }
+
+    #[test]
+    fn test_custom_criteria() {
+        let input = input_generator(EXAMPLE_INPUT);
+        // Always keep the ones, or always the zeroes, finds the largest and smallest
+        assert_eq!(input.calc_rating(|_, _| true), Some(0b11110));
+        assert_eq!(input.calc_rating(|_, _| false), Some(0b00010));
+
+        // Duplicates never get down to a single number, so asking for a bit they don't have is a dead end
+        let input = input_generator("11\n11\n");
+        assert_eq!(input.calc_rating(|_, _| true), Some(0b11));
+        assert_eq!(input.calc_rating(|_, _| false), None);
+    }
+
+    #[test]
+    fn test_ragged() {
+        let result = Diagnostic::<u32>::parser("01\n111\n");
+        assert_eq!(
+            result.err(),
+            Some(nom::Err::Failure(Error::new("111", ErrorKind::LengthValue)))
+        );
+        assert!(Diagnostic::<u32>::parser("011\n11\n011\n").is_err());
+        assert!(Diagnostic::<u32>::parser("  011\n110\n011\n").is_ok());
+    }
+
+    #[test]
+    fn test_wide() {
+        // The example again, but padded out past 64 bits with zeros in front and ones behind
+        let wide: String = EXAMPLE_INPUT
+            .lines()
+            .map(|line| format!("{}{}{}\n", "0".repeat(60), line, "1".repeat(35)))
+            .collect();
+        let input: Diagnostic<u128> = Diagnostic::parser(&wide).unwrap().1;
+        let ones: u128 = (1 << 35) - 1;
+        assert_eq!(
+            input.gamma_epsilon_rate(TiePolicy::Reject),
+            Ok((
+                22 << 35 | ones,
+                (9 << 35) | ((1 << 100) - 1) & !((1 << 40) - 1)
+            ))
+        );
+        assert_eq!(input.oxygen_rating(), 23 << 35 | ones);
+        // Every number has the same padding bits, so the least common one doesn't exist
+        assert_eq!(
+            input.calc_rating(Diagnostic::<u128>::co2_bit_criteria),
+            None
+        );
+
+        // Too wide for a u64, even though the values would fit
+        assert!(Diagnostic::<u64>::parser(&wide).is_err());
+        let narrow: Diagnostic<u64> = Diagnostic::parser("0001\n").unwrap().1;
+        assert_eq!(narrow.oxygen_rating(), 1);
+    }
 }
@@ -70,6 +70,31 @@ use nom::{
     multi::{many1, many_m_n, separated_list1},
     IResult,
 };
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line {
+    Row(usize),
+    Column(usize),
+}
+
+impl Line {
+    fn cells(self, size: usize) -> impl Iterator<Item = (usize, usize)> {
+        (0..size).map(move |i| match self {
+            Line::Row(row) => (row, i),
+            Line::Column(col) => (i, col),
+        })
+    }
+}
+
+// How a board won: on which draw (an index into the numbers), with what number and score, and along which line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Win {
+    draw: usize,
+    number: u32,
+    score: u32,
+    line: Line,
+}
 
 #[derive(Clone)]
 pub struct BingoGame {
@@ -80,76 +105,90 @@ pub struct BingoGame {
 impl BingoGame {
     fn parser(input: &str) -> IResult<&str, Self> {
         let (input, numbers) = separated_list1(trim_start(char(',')), unsigned)(input)?;
-        let (input, boards) = many1(Board::parser)(input)?;
+        // Boards are square, so the first row says how big they all are
+        let size = input
+            .trim_start()
+            .lines()
+            .next()
+            .map_or(0, |row| row.split_whitespace().count());
+        let (input, boards) = many1(Board::parser(size))(input)?;
 
         Ok((input, Self { numbers, boards }))
     }
 
-    fn play_to_win(&mut self) -> u32 {
-        for n in &self.numbers {
-            for b in &mut self.boards {
-                if let Some(score) = b.draw_number(*n) {
-                    return score;
-                }
-            }
+    // Plays out the whole draw for every board, in board order. None for a board that never wins.
+    fn simulate(&self) -> Vec<Option<Win>> {
+        let mut drawn_at = HashMap::new();
+        for (draw, n) in self.numbers.iter().enumerate() {
+            drawn_at.entry(*n).or_insert(draw);
         }
-        panic!("Error: all numbers drawn without any boards winning!");
+        self.boards
+            .iter()
+            .map(|b| b.find_win(&self.numbers, &drawn_at))
+            .collect()
     }
 
-    fn play_to_lose(&mut self) -> u32 {
-        for n in &self.numbers {
-            let mut retain = vec![true; self.boards.len()];
-            let mut last_score = None;
-            for (b, r) in self.boards.iter_mut().zip(retain.iter_mut()) {
-                if let Some(score) = b.draw_number(*n) {
-                    *r = false;
-                    last_score = Some(score);
-                }
-            }
+    // Board indexes and their wins, in the order they win. Boards winning on the same draw stay in board order.
+    fn ranking(&self) -> Vec<(usize, Win)> {
+        let mut ranking: Vec<(usize, Win)> = self
+            .simulate()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, win)| Some((idx, win?)))
+            .collect();
+        ranking.sort_by_key(|(idx, win)| (win.draw, *idx));
+        ranking
+    }
 
-            let mut retain_iter = retain.into_iter();
-            self.boards.retain(|_| retain_iter.next().unwrap());
+    fn nth_winner(&self, n: usize) -> Option<(usize, Win)> {
+        self.ranking().get(n).copied()
+    }
 
-            if self.boards.is_empty() == true {
-                return last_score.unwrap();
-            }
+    fn play_to_win(&self) -> u32 {
+        let (_, win) = self
+            .nth_winner(0)
+            .expect("Error: all numbers drawn without any boards winning!");
+        win.score
+    }
+
+    fn play_to_lose(&self) -> u32 {
+        let ranking = self.ranking();
+        if ranking.len() != self.boards.len() {
+            panic!("Error: all numbers drawn without all boards winning!");
         }
-        panic!("Error: all numbers drawn without all boards winning!");
+        ranking.last().unwrap().1.score
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Board {
-    grid: [[u32; 5]; 5],
-    state: [[bool; 5]; 5],
+    grid: Vec<Vec<u32>>,
+    state: Vec<Vec<bool>>,
 }
 
 impl Board {
-    fn parser(input: &str) -> IResult<&str, Self> {
-        // TODO: pretty sure nom's fill can be used here, but ran into some incompatibilities between Fn and FnMut parser functions
-        // let mut buf = [0u32; 5];
-        // let (input, ()) = fill(trim_start(unsigned), &mut buf)(input)?;
-        let (input, mut grid_vec) = many_m_n(5, 5, many_m_n(5, 5, trim_start(unsigned)))(input)?;
-        let grid = [
-            grid_vec.remove(0).try_into().unwrap(),
-            grid_vec.remove(0).try_into().unwrap(),
-            grid_vec.remove(0).try_into().unwrap(),
-            grid_vec.remove(0).try_into().unwrap(),
-            grid_vec.remove(0).try_into().unwrap(),
-        ];
-
-        Ok((
-            input,
-            Self {
-                grid,
-                state: [[false; 5]; 5],
-            },
-        ))
+    fn parser(size: usize) -> impl FnMut(&str) -> IResult<&str, Self> {
+        move |input| {
+            let (input, grid) =
+                many_m_n(size, size, many_m_n(size, size, trim_start(unsigned)))(input)?;
+
+            Ok((
+                input,
+                Self {
+                    grid,
+                    state: vec![vec![false; size]; size],
+                },
+            ))
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.grid.len()
     }
 
     fn mark_number(&mut self, number: u32) {
-        for row_idx in 0..5 {
-            for col_idx in 0..5 {
+        for row_idx in 0..self.size() {
+            for col_idx in 0..self.size() {
                 if self.grid[row_idx][col_idx] == number {
                     assert!(self.state[row_idx][col_idx] == false); // Bingo shouldn't draw the same number twice
                     self.state[row_idx][col_idx] = true;
@@ -160,24 +199,20 @@ impl Board {
     }
 
     fn winner(&self) -> bool {
-        for row_idx in 0..5 {
-            if (0..5).all(|x| self.state[row_idx][x] == true) {
-                return true;
-            }
-        }
-        for col_idx in 0..5 {
-            if (0..5).all(|x| self.state[x][col_idx] == true) {
-                return true;
-            }
-        }
-
-        false
+        let size = self.size();
+        (0..size)
+            .map(Line::Row)
+            .chain((0..size).map(Line::Column))
+            .any(|line| {
+                line.cells(size)
+                    .all(|(row, col)| self.state[row][col] == true)
+            })
     }
 
     fn calc_score(&self, number: u32) -> u32 {
         let mut sum = 0;
-        for row_idx in 0..5 {
-            for col_idx in 0..5 {
+        for row_idx in 0..self.size() {
+            for col_idx in 0..self.size() {
                 if self.state[row_idx][col_idx] == false {
                     sum += self.grid[row_idx][col_idx];
                 }
@@ -194,6 +229,37 @@ impl Board {
             None
         }
     }
+
+    // Works out the win straight from when each number gets drawn, rather than marking the board draw by draw. A line
+    // is complete on the draw of its last number, and the board wins on its earliest line.
+    fn find_win(&self, numbers: &[u32], drawn_at: &HashMap<u32, usize>) -> Option<Win> {
+        let size = self.size();
+        let (draw, line) = (0..size)
+            .map(Line::Row)
+            .chain((0..size).map(Line::Column))
+            .filter_map(|line| {
+                let complete = line.cells(size).try_fold(0, |latest, (row, col)| {
+                    let draw = *drawn_at.get(&self.grid[row][col])?;
+                    Some(usize::max(latest, draw))
+                })?;
+                Some((complete, line))
+            })
+            .min_by_key(|(draw, _)| *draw)?;
+
+        let unmarked: u32 = self
+            .grid
+            .iter()
+            .flatten()
+            .filter(|n| drawn_at.get(n).is_none_or(|d| *d > draw))
+            .sum();
+        let number = numbers[draw];
+        Some(Win {
+            draw,
+            number,
+            score: unmarked * number,
+            line,
+        })
+    }
 }
 
 #[aoc_generator(day4)]
@@ -203,16 +269,14 @@ pub fn input_generator(input: &str) -> BingoGame {
 
 #[aoc(day4, part1)]
 pub fn part1(input: &BingoGame) -> u32 {
-    let mut game = input.clone();
-    let score = game.play_to_win();
+    let score = input.play_to_win();
     assert_eq!(score, 49686);
     score
 }
 
 #[aoc(day4, part2)]
 pub fn part2(input: &BingoGame) -> u32 {
-    let mut game = input.clone();
-    let score = game.play_to_lose();
+    let score = input.play_to_lose();
     assert_eq!(score, 26878);
     score
 }
@@ -371,15 +435,107 @@ mod test {
 
     #[test]
     fn test_play_to_win() {
-        let mut game = input_generator(EXAMPLE_INPUT);
+        let game = input_generator(EXAMPLE_INPUT);
         let score = game.play_to_win();
         assert_eq!(score, 4512);
     }
 
     #[test]
     fn test_play_to_lose() {
-        let mut game = input_generator(EXAMPLE_INPUT);
+        let game = input_generator(EXAMPLE_INPUT);
         let score = game.play_to_lose();
         assert_eq!(score, 1924);
     }
+
+    #[test]
+    fn test_ranking() {
+        let game = input_generator(EXAMPLE_INPUT);
+        assert_eq!(
+            game.ranking(),
+            [
+                (
+                    2,
+                    Win {
+                        draw: 11,
+                        number: 24,
+                        score: 4512,
+                        line: Line::Row(0)
+                    }
+                ),
+                (
+                    0,
+                    Win {
+                        draw: 13,
+                        number: 16,
+                        score: 2192,
+                        line: Line::Row(2)
+                    }
+                ),
+                (
+                    1,
+                    Win {
+                        draw: 14,
+                        number: 13,
+                        score: 1924,
+                        line: Line::Column(2)
+                    }
+                ),
+            ]
+        );
+        assert_eq!(game.nth_winner(1).unwrap().0, 0);
+        assert_eq!(game.nth_winner(3), None);
+
+        // Same as drawing the numbers one at a time
+        let mut boards = game.boards.clone();
+        for (idx, win) in game.simulate().into_iter().enumerate() {
+            let win = win.unwrap();
+            let scores: Vec<Option<u32>> = game.numbers[..=win.draw]
+                .iter()
+                .map(|n| boards[idx].draw_number(*n))
+                .collect();
+            assert_eq!(scores.iter().position(|s| s.is_some()), Some(win.draw));
+            assert_eq!(scores[win.draw], Some(win.score));
+        }
+    }
+
+    #[test]
+    fn test_small_boards() {
+        let game = input_generator(
+            "\
+1,2,3,4,5
+
+1 2 3
+7 8 9
+10 11 12
+
+9 4 1
+8 5 2
+7 6 3
+
+20 21 22
+23 24 25
+26 27 28",
+        );
+        assert_eq!(game.boards.len(), 3);
+        assert_eq!(
+            game.simulate(),
+            [
+                Some(Win {
+                    draw: 2,
+                    number: 3,
+                    score: 171,
+                    line: Line::Row(0)
+                }),
+                Some(Win {
+                    draw: 2,
+                    number: 3,
+                    score: 117,
+                    line: Line::Column(2)
+                }),
+                None,
+            ]
+        );
+        assert_eq!(game.play_to_win(), 171);
+        assert_eq!(game.ranking().len(), 2);
+    }
 }
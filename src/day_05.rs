@@ -64,7 +64,10 @@
 
 use crate::common::{trim_start, Mode, Point2};
 use nom::{bytes::complete::tag, multi::many1, sequence::separated_pair, IResult};
-use std::{cmp::Ordering, collections::HashMap};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
 
 pub struct Line {
     begin: Point2,
@@ -78,6 +81,153 @@ impl Line {
 
         Ok((input, Self { begin, end }))
     }
+
+    fn is_axis_aligned(&self) -> bool {
+        self.begin.x == self.end.x || self.begin.y == self.end.y
+    }
+}
+
+fn cross(a: Point2, b: Point2) -> i32 {
+    a.x * b.y - a.y * b.x
+}
+
+// A line as the lattice points it covers: start, start + step, ..., start + step * len. The step is the smallest whole
+// move along the line, so any slope works and not just the horizontal, vertical and 45° ones.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: Point2,
+    step: Point2,
+    len: i32,
+}
+
+impl Segment {
+    fn new(line: &Line) -> Self {
+        let delta = line.end - line.begin;
+        let len = num::integer::gcd(delta.x, delta.y);
+        let step = if len == 0 {
+            Point2::origin()
+        } else {
+            Point2 {
+                x: delta.x / len,
+                y: delta.y / len,
+            }
+        };
+        Self {
+            start: line.begin,
+            step,
+            len,
+        }
+    }
+
+    fn point(&self, t: i32) -> Point2 {
+        self.start + (self.step.x * t, self.step.y * t)
+    }
+
+    fn x_range(&self) -> (i32, i32) {
+        let end = self.point(self.len).x;
+        (self.start.x.min(end), self.start.x.max(end))
+    }
+
+    // The t where point(t) == p, if p is a lattice point anywhere on the infinite line
+    fn param(&self, p: Point2) -> Option<i32> {
+        let offset = p - self.start;
+        if self.step == Point2::origin() {
+            return if offset == Point2::origin() {
+                Some(0)
+            } else {
+                None
+            };
+        }
+        if cross(offset, self.step) != 0 {
+            return None;
+        }
+        // The step is as small as it gets, so anything lined up with it is a whole number of steps
+        if self.step.x != 0 {
+            Some(offset.x / self.step.x)
+        } else {
+            Some(offset.y / self.step.y)
+        }
+    }
+
+    fn contains(&self, p: Point2) -> bool {
+        match self.param(p) {
+            Some(t) => (0..=self.len).contains(&t),
+            None => false,
+        }
+    }
+
+    // Every lattice point on both segments
+    fn intersect(&self, other: &Self) -> Vec<Point2> {
+        if other.len == 0 {
+            return if self.contains(other.start) == true {
+                vec![other.start]
+            } else {
+                Vec::new()
+            };
+        }
+        if self.len == 0 {
+            return other.intersect(self);
+        }
+
+        let denom = cross(self.step, other.step);
+        let offset = other.start - self.start;
+        if denom == 0 {
+            // Parallel, so they either share a stretch of the same line or don't meet at all
+            let begin = match self.param(other.start) {
+                Some(t) => t,
+                None => return Vec::new(),
+            };
+            let end = if other.step == self.step {
+                begin + other.len
+            } else {
+                begin - other.len
+            };
+            let low = begin.min(end).max(0);
+            let high = begin.max(end).min(self.len);
+            (low..=high).map(|t| self.point(t)).collect()
+        } else {
+            // Solve start + i * step == other.start + j * other.step, which only counts if both are whole numbers
+            let i = cross(offset, other.step);
+            let j = cross(offset, self.step);
+            if i % denom != 0 || j % denom != 0 {
+                return Vec::new();
+            }
+            let (i, j) = (i / denom, j / denom);
+            if (0..=self.len).contains(&i) && (0..=other.len).contains(&j) {
+                vec![self.point(i)]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn segments(lines: &[Line], mode: Mode) -> Vec<Segment> {
+    // Part 1 ignores lines that aren't horizontal or vertical
+    lines
+        .iter()
+        .filter(|line| mode == Mode::M2 || line.is_axis_aligned() == true)
+        .map(Segment::new)
+        .collect()
+}
+
+// Counts the points covered more than once from where the lines meet, so the work follows the number of lines rather
+// than their length. Sweeping left to right, each line is only checked against later ones whose x range reaches it.
+fn count_overlaps(lines: &[Line], mode: Mode) -> usize {
+    let mut segments = segments(lines, mode);
+    segments.sort_by_key(|s| s.x_range().0);
+
+    let mut overlaps = HashSet::new();
+    for (idx, a) in segments.iter().enumerate() {
+        let (_, right) = a.x_range();
+        for b in segments[idx + 1..]
+            .iter()
+            .take_while(|b| b.x_range().0 <= right)
+        {
+            overlaps.extend(a.intersect(b));
+        }
+    }
+    overlaps.len()
 }
 
 struct Field {
@@ -85,6 +235,8 @@ struct Field {
 }
 
 impl Field {
+    // Walks each line one cell at a time, which only works for horizontal, vertical and 45° lines. Kept apart from
+    // Segment so it can check the intersection counting.
     fn from_lines(lines: &[Line], mode: Mode) -> Self {
         let mut area = HashMap::new();
 
@@ -150,16 +302,14 @@ pub fn input_generator(input: &str) -> Vec<Line> {
 
 #[aoc(day5, part1)]
 pub fn part1(input: &[Line]) -> usize {
-    let field = Field::from_lines(input, Mode::M1);
-    let count = field.count_overlaps();
+    let count = count_overlaps(input, Mode::M1);
     assert_eq!(count, 7142);
     count
 }
 
 #[aoc(day5, part2)]
 pub fn part2(input: &[Line]) -> usize {
-    let field = Field::from_lines(input, Mode::M2);
-    let count = field.count_overlaps();
+    let count = count_overlaps(input, Mode::M2);
     assert_eq!(count, 20012);
     count
 }
@@ -167,6 +317,7 @@ pub fn part2(input: &[Line]) -> usize {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::common::SplitMix64;
 
     static EXAMPLE_INPUT: &str = "\
 0,9 -> 5,9
@@ -245,4 +396,72 @@ mod test {
 "
         );
     }
+
+    #[test]
+    fn test_sweep_matches_raster() {
+        let input = input_generator(EXAMPLE_INPUT);
+        assert_eq!(count_overlaps(&input, Mode::M1), 5);
+        assert_eq!(count_overlaps(&input, Mode::M2), 12);
+
+        // Lots of short random lines in all eight directions, so plenty of crossings and shared stretches
+        let mut rng = SplitMix64::new(5);
+        for _ in 0..50 {
+            let lines: Vec<Line> = (0..20)
+                .map(|_| {
+                    let begin: Point2 = (rng.range(0, 11), rng.range(0, 11)).into();
+                    let (dx, dy) = match rng.range(0, 7) {
+                        0 => (1, 0),
+                        1 => (-1, 0),
+                        2 => (0, 1),
+                        3 => (0, -1),
+                        4 => (1, 1),
+                        5 => (1, -1),
+                        6 => (-1, 1),
+                        _ => (-1, -1),
+                    };
+                    let len = rng.range(0, 7);
+                    Line {
+                        begin,
+                        end: begin + (dx * len, dy * len),
+                    }
+                })
+                .collect();
+            for mode in [Mode::M1, Mode::M2] {
+                assert_eq!(
+                    count_overlaps(&lines, mode),
+                    Field::from_lines(&lines, mode).count_overlaps()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_intersect() {
+        let segment = |input: &str| Segment::new(&Line::parser(input).unwrap().1);
+        let points = |a: &str, b: &str| {
+            let mut points = segment(a).intersect(&segment(b));
+            points.sort_unstable();
+            points
+        };
+
+        assert_eq!(points("0,0 -> 4,2", "0,2 -> 4,0"), [(2, 1).into()]);
+        // Crosses between lattice points
+        assert_eq!(points("0,0 -> 3,1", "0,1 -> 3,0"), []);
+        // Same line, facing opposite ways
+        assert_eq!(
+            points("0,0 -> 6,0", "8,0 -> 3,0"),
+            [(3, 0).into(), (4, 0).into(), (5, 0).into(), (6, 0).into()]
+        );
+        // Parallel, one step apart
+        assert_eq!(points("0,0 -> 4,2", "1,1 -> 5,3"), []);
+        assert_eq!(
+            points("0,0 -> 6,3", "8,4 -> 4,2"),
+            [(4, 2).into(), (6, 3).into()]
+        );
+        assert_eq!(points("2,2 -> 2,2", "0,0 -> 4,4"), [(2, 2).into()]);
+        assert_eq!(points("0,0 -> 4,4", "3,2 -> 3,2"), []);
+        assert_eq!(points("1,1 -> 1,1", "1,1 -> 1,1"), [(1, 1).into()]);
+        // Would meet if they were longer
+        assert_eq!(points("0,0 -> 2,0", "5,-3 -> 5,3"), []);
+    }
 }
@@ -58,40 +58,161 @@
 */
 
 use crate::common::unsigned;
-use nom::{character::complete::char, multi::separated_list1, IResult};
+use nom::{
+    character::complete::char,
+    combinator::{cut, verify},
+    multi::separated_list1,
+    IResult,
+};
+use num::{One, Zero};
+
+// The timer a fish goes back to after giving birth, and the timer a newborn starts on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lifecycle {
+    reset: usize,
+    newborn: usize,
+}
 
-#[derive(Clone)]
-pub struct FishSchool {
-    counts: [u64; 9],
+impl Lifecycle {
+    pub const LANTERNFISH: Self = Self {
+        reset: 6,
+        newborn: 8,
+    };
+
+    fn timers(&self) -> usize {
+        self.reset.max(self.newborn) + 1
+    }
+}
+
+// How one day moves the counts along: next[to] is the sum of cells[to][from] * counts[from]
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Transition<T> {
+    size: usize,
+    cells: Vec<T>,
 }
 
-impl FishSchool {
-    fn parser(input: &str) -> IResult<&str, Self> {
-        let (input, list): (_, Vec<usize>) = separated_list1(char(','), unsigned)(input)?;
+impl<T> Transition<T>
+where
+    T: Clone + Zero + One,
+{
+    fn identity(size: usize) -> Self {
+        let mut cells = vec![T::zero(); size * size];
+        for i in 0..size {
+            cells[i * size + i] = T::one();
+        }
+        Self { size, cells }
+    }
+
+    fn one_day(lifecycle: Lifecycle) -> Self {
+        let size = lifecycle.timers();
+        let mut cells = vec![T::zero(); size * size];
+        for timer in 1..size {
+            cells[(timer - 1) * size + timer] = T::one();
+        }
+        for to in [lifecycle.reset, lifecycle.newborn] {
+            let cell = &mut cells[to * size];
+            *cell = cell.clone() + T::one();
+        }
+        Self { size, cells }
+    }
+
+    fn get(&self, to: usize, from: usize) -> &T {
+        &self.cells[to * self.size + from]
+    }
 
-        let mut counts = [0; 9];
-        for fish in list {
-            counts[fish] += 1;
+    fn then(&self, other: &Self) -> Self {
+        let size = self.size;
+        let mut cells = Vec::with_capacity(size * size);
+        for to in 0..size {
+            for from in 0..size {
+                let cell = (0..size).fold(T::zero(), |sum, via| {
+                    sum + other.get(to, via).clone() * self.get(via, from).clone()
+                });
+                cells.push(cell);
+            }
         }
+        Self { size, cells }
+    }
 
-        Ok((input, Self { counts }))
+    fn apply(&self, counts: &[T]) -> Vec<T> {
+        (0..self.size)
+            .map(|to| {
+                counts.iter().enumerate().fold(T::zero(), |sum, (from, n)| {
+                    sum + self.get(to, from).clone() * n.clone()
+                })
+            })
+            .collect()
     }
 
+    fn pow(&self, mut exp: usize) -> Self {
+        let mut result = Self::identity(self.size);
+        let mut square = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.then(&square);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                square = square.then(&square);
+            }
+        }
+        result
+    }
+}
+
+// Counts can be u64 for the puzzle, or num::BigUint once the school outgrows that
+#[derive(Clone)]
+pub struct FishSchool<T = u64> {
+    lifecycle: Lifecycle,
+    counts: Vec<T>,
+}
+
+impl<T> FishSchool<T>
+where
+    T: Clone + Zero + One,
+{
+    fn parser(lifecycle: Lifecycle) -> impl FnMut(&str) -> IResult<&str, Self> {
+        move |input| {
+            // A timer past the end of the lifecycle can't be counted anywhere
+            let timers = lifecycle.timers();
+            let timer = verify(unsigned, |fish: &usize| *fish < timers);
+            let (input, list): (_, Vec<usize>) = separated_list1(char(','), cut(timer))(input)?;
+
+            let mut counts = vec![T::zero(); lifecycle.timers()];
+            for fish in list {
+                counts[fish] = counts[fish].clone() + T::one();
+            }
+
+            Ok((input, Self { lifecycle, counts }))
+        }
+    }
+
+    // Jumps ahead by squaring the one day transition, so a huge number of days costs about as much as a few dozen
     fn step(&mut self, steps: usize) {
+        let transition = Transition::one_day(self.lifecycle).pow(steps);
+        self.counts = transition.apply(&self.counts);
+    }
+
+    fn step_daily(&mut self, steps: usize) {
         for _ in 0..steps {
             self.counts.rotate_left(1);
-            self.counts[6] += self.counts[8];
+            // Every fish at zero wrapped round to the end, where nobody else can be yet
+            let last = self.counts.len() - 1;
+            let parents = std::mem::replace(&mut self.counts[last], T::zero());
+            for timer in [self.lifecycle.reset, self.lifecycle.newborn] {
+                self.counts[timer] = self.counts[timer].clone() + parents.clone();
+            }
         }
     }
 
-    fn total_fish(&self) -> u64 {
-        self.counts.iter().sum()
+    fn total_fish(&self) -> T {
+        self.counts.iter().fold(T::zero(), |sum, n| sum + n.clone())
     }
 }
 
 #[aoc_generator(day6)]
 pub fn input_generator(input: &str) -> FishSchool {
-    FishSchool::parser(input).unwrap().1
+    FishSchool::parser(Lifecycle::LANTERNFISH)(input).unwrap().1
 }
 
 #[aoc(day6, part1)]
@@ -115,6 +236,7 @@ pub fn part2(input: &FishSchool) -> u64 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use num::BigUint;
 
     static EXAMPLE_INPUT: &str = "3,4,3,1,2";
 
@@ -154,4 +276,75 @@ mod test {
         school.step(256);
         assert_eq!(school.total_fish(), 26984457539);
     }
+
+    #[test]
+    fn test_step_daily() {
+        let mut school = input_generator(EXAMPLE_INPUT);
+        school.step_daily(3);
+        assert_eq!(school.counts, [2, 1, 0, 0, 0, 1, 1, 1, 1]);
+        school.step_daily(15);
+        assert_eq!(school.counts, [3, 5, 3, 2, 2, 1, 5, 1, 4]);
+
+        for days in [0, 1, 7, 9, 80, 256] {
+            let mut daily = input_generator(EXAMPLE_INPUT);
+            let mut fast = daily.clone();
+            daily.step_daily(days);
+            fast.step(days);
+            assert_eq!(daily.counts, fast.counts);
+        }
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        // Every fish splits in two each day
+        let doubling = Lifecycle {
+            reset: 0,
+            newborn: 0,
+        };
+        let mut school: FishSchool = FishSchool::parser(doubling)("0,0,0").unwrap().1;
+        school.step(10);
+        assert_eq!(school.counts, [3 * 1024]);
+
+        // No room for a timer of 3
+        let result = FishSchool::<u64>::parser(doubling)("0,0,3");
+        assert_eq!(
+            result.err(),
+            Some(nom::Err::Failure(nom::error::Error::new(
+                "3",
+                nom::error::ErrorKind::Verify
+            )))
+        );
+        assert!(FishSchool::<u64>::parser(doubling)("3").is_err());
+
+        // Slower breeders, with newborns ready sooner than their parents
+        let lifecycle = Lifecycle {
+            reset: 4,
+            newborn: 2,
+        };
+        let mut daily: FishSchool = FishSchool::parser(lifecycle)("4,1,0").unwrap().1;
+        assert_eq!(daily.counts, [1, 1, 0, 0, 1]);
+        let mut fast = daily.clone();
+        daily.step_daily(1);
+        assert_eq!(daily.counts, [1, 0, 1, 1, 1]);
+        daily.step_daily(99);
+        fast.step(100);
+        assert_eq!(daily.counts, fast.counts);
+    }
+
+    #[test]
+    fn test_big_school() {
+        let mut school: FishSchool<BigUint> =
+            FishSchool::parser(Lifecycle::LANTERNFISH)(EXAMPLE_INPUT)
+                .unwrap()
+                .1;
+        let mut daily = school.clone();
+        school.step(256);
+        assert_eq!(school.total_fish(), BigUint::from(26984457539u64));
+
+        // Far past what a u64 can hold
+        school.step(1744);
+        daily.step_daily(2000);
+        assert_eq!(school.counts, daily.counts);
+        assert!(school.total_fish().bits() > 200);
+    }
 }
@@ -336,78 +336,122 @@
     If you can calculate the exact moments when the octopuses will all flash simultaneously, you should be able to navigate through the cavern. What is the first step during which all octopuses flash?
 */
 
-use crate::common::Point2;
+use crate::common::{Boundary, Grid, Neighborhood, Rule};
 use nom::IResult;
-use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Octopus {
+    energy: u32,
+    flashed: bool,
+}
+
+impl Octopus {
+    fn ready_to_flash(&self) -> bool {
+        self.energy > 9 && self.flashed == false
+    }
+}
+
+// Each step is split into rules, each updating every octopus at once
+struct Charge;
+struct Flash;
+struct Reset;
+
+impl Rule for Charge {
+    type Cell = Octopus;
+
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::Custom(Vec::new())
+    }
+
+    fn next(&self, cell: &Octopus, _neighbors: &[Octopus]) -> Octopus {
+        Octopus {
+            energy: cell.energy + 1,
+            flashed: false,
+        }
+    }
+}
+
+// One wave of flashes. Repeat until nothing changes to get the whole chain reaction.
+impl Rule for Flash {
+    type Cell = Octopus;
+
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::Moore
+    }
+
+    fn next(&self, cell: &Octopus, neighbors: &[Octopus]) -> Octopus {
+        let flashing = neighbors.iter().filter(|n| n.ready_to_flash()).count() as u32;
+        Octopus {
+            energy: cell.energy + flashing,
+            flashed: cell.flashed || cell.ready_to_flash(),
+        }
+    }
+}
+
+impl Rule for Reset {
+    type Cell = Octopus;
+
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::Custom(Vec::new())
+    }
+
+    fn next(&self, cell: &Octopus, _neighbors: &[Octopus]) -> Octopus {
+        if cell.flashed == true {
+            Octopus {
+                energy: 0,
+                flashed: false,
+            }
+        } else {
+            *cell
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct OctoGrid {
-    grid: HashMap<Point2, u32>,
+    grid: Grid<Octopus>,
 }
 
 impl OctoGrid {
     fn parser(input: &str) -> IResult<&str, Self> {
-        let mut grid = HashMap::new();
-        for (y, line) in input.lines().enumerate() {
-            for (x, c) in line.chars().enumerate() {
-                let energy = c.to_digit(10).unwrap();
-                let p = (x as i32, y as i32).into();
-                grid.insert(p, energy);
-            }
-        }
+        let rows = input
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(|c| Octopus {
+                        energy: c.to_digit(10).unwrap(),
+                        flashed: false,
+                    })
+                    .collect()
+            })
+            .collect();
+        let outside = Octopus {
+            energy: 0,
+            flashed: true, // So they never flash
+        };
+        let grid = Grid::from_rows(rows, Boundary::Fixed(outside));
         Ok((input, Self { grid }))
     }
 
     fn step(&mut self, steps: u32) -> (usize, Option<u32>) {
         let mut count_flashes = 0;
 
-        let mut to_flash: Vec<Point2> = Vec::new();
-        let mut flashed: HashSet<Point2> = HashSet::new();
-
         for i in 0..steps {
-            to_flash.clear();
-            flashed.clear();
-
             // First, increase energy level
-            let range = Point2::get_range(self.grid.keys()).unwrap();
-            for x in range.x.0..=range.x.1 {
-                for y in range.y.0..=range.y.1 {
-                    let p = Point2 { x, y };
-                    if let Some(v) = self.grid.get_mut(&p) {
-                        *v += 1;
-                        if *v > 9 {
-                            to_flash.push(p);
-                            flashed.insert(p);
-                        }
-                    } else {
-                        panic!("Assume the grid is full");
-                    }
-                }
-            }
+            self.grid.step(&Charge);
 
             // Flash
-            while let Some(p) = to_flash.pop() {
-                for adj in p.adjacents() {
-                    if let Some(v) = self.grid.get_mut(&adj) {
-                        *v += 1;
-                        if *v > 9 && flashed.contains(&adj) == false {
-                            to_flash.push(adj);
-                            flashed.insert(adj);
-                        }
-                    }
-                }
-            }
+            while self.grid.step(&Flash) {}
 
             // Count flashes, and a quick check to see if everything flashed
-            count_flashes += flashed.len();
-            if flashed.len() == self.grid.len() {
+            let flashed = self.grid.iter().filter(|(_, o)| o.flashed).count();
+            count_flashes += flashed;
+            if flashed == self.grid.width() * self.grid.height() {
                 return (count_flashes, Some(i + 1)); // In the puzzle, steps are 1-based
             }
 
             // Last, reset energy to zero if they flashed
-            for oct in flashed.drain() {
-                self.grid.insert(oct, 0);
-            }
+            self.grid.step(&Reset);
         }
 
         (count_flashes, None)
@@ -416,14 +460,9 @@ impl OctoGrid {
 
 impl std::fmt::Display for OctoGrid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let range = Point2::get_range(self.grid.keys()).unwrap();
-        for y in range.y.0..=range.y.1 {
-            for x in range.x.0..=range.x.1 {
-                if let Some(t) = self.grid.get(&Point2 { x, y }) {
-                    write!(f, "{}", t)?;
-                } else {
-                    write!(f, ".")?;
-                }
+        for row in self.grid.rows() {
+            for oct in row {
+                write!(f, "{}", oct.energy)?;
             }
             writeln!(f)?;
         }
@@ -287,7 +287,7 @@ impl Line {
                         Element::Number(right_n),
                         Element::PairClose,
                     ];
-                    self.data.splice(idx..idx + 1, new.into_iter());
+                    self.data.splice(idx..idx + 1, new);
                     return true;
                 }
             }
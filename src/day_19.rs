@@ -362,7 +362,7 @@
     What is the largest Manhattan distance between any two scanners?
 */
 
-use crate::common::{unsigned, Point3};
+use crate::common::{unsigned, Point3, Range3, Rotation3, SplitMix64};
 use nom::{
     bytes::complete::tag,
     character::complete::multispace0,
@@ -370,39 +370,18 @@ use nom::{
     sequence::{preceded, tuple},
     IResult,
 };
-use std::{cmp::Ordering, collections::HashSet};
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum XYZ {
-    X,
-    Y,
-    Z,
-    NX,
-    NY,
-    NZ,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Orientation {
-    facing: XYZ,
-    n: u32,
-}
-
-impl Orientation {
-    fn new() -> Self {
-        Self {
-            facing: XYZ::NZ,
-            n: 0,
-        }
-    }
-}
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Scanner {
     id: u32,
     beacons: Vec<Point3>,
     position: Option<Point3>,
-    orientation: Option<Orientation>,
+    orientation: Option<Rotation3>,
     distances: HashSet<u32>,
 }
 
@@ -441,21 +420,8 @@ impl Scanner {
         self.distances = distances;
     }
 
-    fn orient(&self, orientation: Orientation) -> Self {
-        let mut beacons = Vec::new();
-        for p in &self.beacons {
-            let new_p = match orientation.facing {
-                XYZ::X => rotate_point_around_axis(p, XYZ::Y, 3),
-                XYZ::Y => rotate_point_around_axis(p, XYZ::X, 1),
-                XYZ::Z => rotate_point_around_axis(p, XYZ::Y, 2),
-                XYZ::NX => rotate_point_around_axis(p, XYZ::Y, 1),
-                XYZ::NY => rotate_point_around_axis(p, XYZ::X, 3),
-                XYZ::NZ => *p,
-            };
-            let new_p = rotate_point_around_axis(&new_p, orientation.facing, orientation.n);
-            beacons.push(new_p);
-        }
-
+    fn orient(&self, orientation: Rotation3) -> Self {
+        let mut beacons: Vec<Point3> = self.beacons.iter().map(|p| orientation.apply(*p)).collect();
         beacons.sort_unstable();
 
         Self {
@@ -474,7 +440,10 @@ impl Scanner {
                 let offset = self_p - other_p;
                 let mut count = 0;
                 for (i, check_p) in other.beacons.iter().enumerate() {
-                    if (other.beacons.len() - i) < (overlap_criteria - count) as usize {
+                    if count >= overlap_criteria {
+                        // Already found enough, no need to keep counting
+                        break;
+                    } else if (other.beacons.len() - i) < (overlap_criteria - count) as usize {
                         // Not enough beacons left to possibly reach the criteria count
                         break;
                     } else {
@@ -485,10 +454,8 @@ impl Scanner {
                     }
                 }
 
-                match count.cmp(&overlap_criteria) {
-                    Ordering::Equal => return Some(offset),
-                    Ordering::Greater => panic!("They overlap suspiciously well"),
-                    Ordering::Less => (),
+                if count >= overlap_criteria {
+                    return Some(offset);
                 }
             }
         }
@@ -497,15 +464,13 @@ impl Scanner {
     }
 
     fn check_overlap_oriented(&self, other: &Self, overlap_criteria: u32) -> Option<Self> {
-        for facing in [XYZ::X, XYZ::Y, XYZ::Z, XYZ::NX, XYZ::NY, XYZ::NZ] {
-            for n in 0..4 {
-                let oriented = other.orient(Orientation { facing, n });
-                if let Some(s) = self.check_overlap(&oriented, overlap_criteria) {
-                    return Some(Self {
-                        position: self.position.map(|x| x + s),
-                        ..oriented
-                    });
-                }
+        for orientation in Rotation3::all() {
+            let oriented = other.orient(orientation);
+            if let Some(s) = self.check_overlap(&oriented, overlap_criteria) {
+                return Some(Self {
+                    position: self.position.map(|x| x + s),
+                    ..oriented
+                });
             }
         }
         None
@@ -516,47 +481,15 @@ impl Scanner {
     }
 }
 
-fn rotate_point_around_axis(p: &Point3, axis: XYZ, n: u32) -> Point3 {
-    assert!(n < 4);
-    let mut new_p = *p;
-    for _ in 0..n {
-        new_p = match axis {
-            XYZ::X => Point3 {
-                x: new_p.x,
-                y: -new_p.z,
-                z: new_p.y,
-            },
-            XYZ::Y => Point3 {
-                x: new_p.z,
-                y: new_p.y,
-                z: -new_p.x,
-            },
-            XYZ::Z => Point3 {
-                x: -new_p.y,
-                y: new_p.x,
-                z: new_p.z,
-            },
-            XYZ::NX => Point3 {
-                x: new_p.x,
-                y: new_p.z,
-                z: -new_p.y,
-            },
-            XYZ::NY => Point3 {
-                x: -new_p.z,
-                y: new_p.y,
-                z: new_p.x,
-            },
-            XYZ::NZ => Point3 {
-                x: new_p.y,
-                y: -new_p.x,
-                z: new_p.z,
-            },
-        }
+// On failure, returns the ids of the scanners that couldn't be placed relative to the others
+fn find_all_positions(
+    scanners: &[Scanner],
+    overlap_criteria: u32,
+) -> Result<Vec<Scanner>, Vec<u32>> {
+    if scanners.is_empty() == true {
+        return Ok(Vec::new());
     }
-    new_p
-}
 
-fn find_all_positions(scanners: &[Scanner]) -> Vec<Scanner> {
     let mut undetermined = scanners.to_vec();
     for scanner in &mut undetermined {
         scanner.gen_distances();
@@ -564,9 +497,13 @@ fn find_all_positions(scanners: &[Scanner]) -> Vec<Scanner> {
 
     let mut origin = undetermined.remove(0);
     origin.position = Some(Point3::origin());
-    origin.orientation = Some(Orientation::new());
+    origin.orientation = Some(Rotation3::IDENTITY);
     let mut oriented = vec![origin];
 
+    // If N points overlap there are N choose 2 distances between them. Plus one because we didn't remove the
+    // identity distance.
+    let distance_criteria = overlap_criteria * overlap_criteria.saturating_sub(1) / 2 + 1;
+
     let mut tried: HashSet<(u32, u32)> = HashSet::new();
 
     let mut keep: Vec<bool> = Vec::new();
@@ -579,10 +516,8 @@ fn find_all_positions(scanners: &[Scanner]) -> Vec<Scanner> {
                     continue;
                 }
 
-                // 67 because if 12 points overlap there are 66 (12 choose 2) distances between them.
-                // Plus one because we didn't remove the identity distance.
-                if oriented[i].check_overlap_by_distance(undet, 67) == true {
-                    if let Some(s) = oriented[i].check_overlap_oriented(undet, 12) {
+                if oriented[i].check_overlap_by_distance(undet, distance_criteria) == true {
+                    if let Some(s) = oriented[i].check_overlap_oriented(undet, overlap_criteria) {
                         oriented.push(s);
                         keep.push(false);
                         continue 'undet;
@@ -594,26 +529,418 @@ fn find_all_positions(scanners: &[Scanner]) -> Vec<Scanner> {
             keep.push(true);
         }
 
+        if keep.iter().all(|k| *k == true) {
+            // Nothing new was placed this time around so nothing else ever will be
+            break;
+        }
+
         let mut keep_iter = keep.iter();
         undetermined.retain(|_| *keep_iter.next().unwrap());
+    }
 
-        if undetermined.is_empty() == true {
-            break;
+    if undetermined.is_empty() == true {
+        Ok(oriented)
+    } else {
+        Err(undetermined.iter().map(|s| s.id).collect())
+    }
+}
+
+// Squared side lengths of a triangle of beacons, shortest first. These don't change with the scanner's
+// position or orientation so the same triangle seen by two scanners has the same fingerprint.
+type Fingerprint = [i64; 3];
+
+// The beacon indexes of the same triangle as seen by two different scanners
+type TrianglePair = ([usize; 3], [usize; 3]);
+
+fn squared_distance(a: Point3, b: Point3) -> i64 {
+    let d = a - b;
+    (d.x as i64).pow(2) + (d.y as i64).pow(2) + (d.z as i64).pow(2)
+}
+
+impl Scanner {
+    fn fingerprints(&self) -> Vec<(Fingerprint, [usize; 3])> {
+        // The beacon indexes are ordered to match the fingerprint: each is the vertex opposite the corresponding side.
+        let mut fingerprints = Vec::new();
+        let n = self.beacons.len();
+        for a in 0..n {
+            for b in a + 1..n {
+                for c in b + 1..n {
+                    let p = |i: usize| self.beacons[i];
+                    let mut sides = [
+                        (squared_distance(p(b), p(c)), a),
+                        (squared_distance(p(a), p(c)), b),
+                        (squared_distance(p(a), p(b)), c),
+                    ];
+                    sides.sort_unstable();
+                    if sides[0].0 == sides[1].0 || sides[1].0 == sides[2].0 {
+                        // Can't tell which vertex is which, so it's no use for finding the orientation
+                        continue;
+                    }
+                    fingerprints.push((
+                        [sides[0].0, sides[1].0, sides[2].0],
+                        [sides[0].1, sides[1].1, sides[2].1],
+                    ));
+                }
+            }
+        }
+        fingerprints
+    }
+
+    // Find the transform taking points seen by other into this scanner's frame of reference, using pairs of
+    // matching triangles (this scanner's beacon indexes, other's beacon indexes).
+    fn align_by_triangles(
+        &self,
+        other: &Self,
+        triangles: &[TrianglePair],
+        overlap_criteria: u32,
+    ) -> Option<(Rotation3, Point3)> {
+        let mut votes: HashMap<(Rotation3, Point3), u32> = HashMap::new();
+        for (a, b) in triangles {
+            let [a0, a1, a2] = a.map(|i| self.beacons[i]);
+            let [b0, b1, b2] = b.map(|i| other.beacons[i]);
+            if let Some(rotation) =
+                Rotation3::all().find(|r| r * (b1 - b0) == a1 - a0 && r * (b2 - b0) == a2 - a0)
+            {
+                let offset = a0 - rotation * b0;
+                *votes.entry((rotation, offset)).or_default() += 1;
+            }
+        }
+
+        // Confirm the most popular transforms by checking that enough beacons actually line up
+        let mut candidates: Vec<((Rotation3, Point3), u32)> = votes.into_iter().collect();
+        candidates.sort_unstable_by_key(|(_, count)| Reverse(*count));
+        let beacons: HashSet<Point3> = self.beacons.iter().copied().collect();
+        candidates
+            .into_iter()
+            .map(|(transform, _)| transform)
+            .find(|(rotation, offset)| {
+                let count = other
+                    .beacons
+                    .iter()
+                    .filter(|p| beacons.contains(&(rotation * *p + offset)))
+                    .count();
+                count >= overlap_criteria as usize
+            })
+    }
+}
+
+// Same as find_all_positions, but candidate scanner pairs and their orientations come straight from triangles that
+// both scanners can see instead of trying every orientation and offset.
+fn find_all_positions_indexed(
+    scanners: &[Scanner],
+    overlap_criteria: u32,
+) -> Result<Vec<Scanner>, Vec<u32>> {
+    if scanners.is_empty() == true {
+        return Ok(Vec::new());
+    }
+
+    // Index every scanner's triangles by fingerprint
+    let mut index: HashMap<Fingerprint, Vec<(usize, [usize; 3])>> = HashMap::new();
+    for (s, scanner) in scanners.iter().enumerate() {
+        for (fingerprint, triangle) in scanner.fingerprints() {
+            index.entry(fingerprint).or_default().push((s, triangle));
+        }
+    }
+
+    // Collect the triangles shared between each pair of scanners
+    let mut shared: HashMap<(usize, usize), Vec<TrianglePair>> = HashMap::new();
+    for entries in index.values() {
+        for (i, (sa, ta)) in entries.iter().enumerate() {
+            for (sb, tb) in &entries[i + 1..] {
+                if sa != sb {
+                    shared.entry((*sa, *sb)).or_default().push((*ta, *tb));
+                    shared.entry((*sb, *sa)).or_default().push((*tb, *ta));
+                }
+            }
+        }
+    }
+
+    // Place scanners outwards from the first, chaining each newly found transform onto its already placed neighbor
+    let mut poses: Vec<Option<(Rotation3, Point3)>> = vec![None; scanners.len()];
+    poses[0] = Some((Rotation3::IDENTITY, Point3::origin()));
+    let mut placed_order = vec![0];
+    let mut i = 0;
+    while i < placed_order.len() {
+        let a = placed_order[i];
+        let (rotation_a, position_a) = poses[a].unwrap();
+        for b in 0..scanners.len() {
+            if poses[b].is_some() == true {
+                continue;
+            }
+            if let Some(triangles) = shared.get(&(a, b)) {
+                if let Some((rotation, offset)) =
+                    scanners[a].align_by_triangles(&scanners[b], triangles, overlap_criteria)
+                {
+                    poses[b] = Some((rotation_a * rotation, position_a + rotation_a * offset));
+                    placed_order.push(b);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let unplaceable: Vec<u32> = scanners
+        .iter()
+        .zip(&poses)
+        .filter(|(_, pose)| pose.is_none())
+        .map(|(s, _)| s.id)
+        .collect();
+    if unplaceable.is_empty() == false {
+        return Err(unplaceable);
+    }
+
+    let oriented = placed_order
+        .into_iter()
+        .map(|s| {
+            let (rotation, position) = poses[s].unwrap();
+            Scanner {
+                position: Some(position),
+                ..scanners[s].orient(rotation)
+            }
+        })
+        .collect();
+    Ok(oriented)
+}
+
+struct ScannerPose {
+    id: u32,
+    position: Point3,
+    rotation: Rotation3,
+}
+
+struct BeaconMap {
+    scanners: Vec<ScannerPose>,
+    beacons: Vec<Point3>,
+}
+
+impl BeaconMap {
+    fn assemble(scanners: &[Scanner], overlap_criteria: u32) -> Result<Self, Vec<u32>> {
+        let oriented = find_all_positions_indexed(scanners, overlap_criteria)?;
+
+        let mut poses: Vec<ScannerPose> = oriented
+            .iter()
+            .map(|s| ScannerPose {
+                id: s.id,
+                position: s.position.unwrap(),
+                rotation: s.orientation.unwrap(),
+            })
+            .collect();
+        poses.sort_unstable_by_key(|pose| pose.id);
+
+        let mut beacons: Vec<Point3> = Vec::new();
+        for s in &oriented {
+            beacons.extend(s.beacons.iter().map(|p| p + s.position.unwrap()));
         }
+        beacons.sort_unstable_by(Point3::cmp_xyz);
+        beacons.dedup();
+
+        Ok(Self {
+            scanners: poses,
+            beacons,
+        })
     }
 
-    oriented
+    fn to_csv(&self) -> String {
+        // One row per scanner and per beacon. Scanners also have their rotation matrix, row by row.
+        let mut csv = String::from("kind,id,x,y,z,r00,r01,r02,r10,r11,r12,r20,r21,r22\n");
+        for pose in &self.scanners {
+            let p = pose.position;
+            let m = pose.rotation.matrix();
+            let matrix: Vec<String> = m.iter().flatten().map(|v| v.to_string()).collect();
+            csv.push_str(&format!(
+                "scanner,{},{},{},{},{}\n",
+                pose.id,
+                p.x,
+                p.y,
+                p.z,
+                matrix.join(",")
+            ));
+        }
+        for p in &self.beacons {
+            csv.push_str(&format!("beacon,,{},{},{},,,,,,,,,\n", p.x, p.y, p.z));
+        }
+        csv
+    }
+
+    fn to_ply(&self) -> String {
+        // Beacons are white and scanners are red
+        let mut ply = String::from("ply\nformat ascii 1.0\n");
+        ply.push_str(&format!(
+            "element vertex {}\n",
+            self.beacons.len() + self.scanners.len()
+        ));
+        ply.push_str("property int x\nproperty int y\nproperty int z\n");
+        ply.push_str("property uchar red\nproperty uchar green\nproperty uchar blue\n");
+        ply.push_str("end_header\n");
+        for p in &self.beacons {
+            ply.push_str(&format!("{} {} {} 255 255 255\n", p.x, p.y, p.z));
+        }
+        for pose in &self.scanners {
+            let p = pose.position;
+            ply.push_str(&format!("{} {} {} 255 0 0\n", p.x, p.y, p.z));
+        }
+        ply
+    }
+
+    fn to_obj(&self) -> String {
+        // Vertices are numbered from 1 in the order they're listed, beacons first then scanners
+        let mut obj = String::new();
+        for p in &self.beacons {
+            obj.push_str(&format!("v {} {} {}\n", p.x, p.y, p.z));
+        }
+        for pose in &self.scanners {
+            let p = pose.position;
+            obj.push_str(&format!("v {} {} {}\n", p.x, p.y, p.z));
+        }
+
+        let beacon_count = self.beacons.len();
+        let beacon_idxs: Vec<String> = (1..=beacon_count).map(|i| i.to_string()).collect();
+        obj.push_str(&format!("o beacons\np {}\n", beacon_idxs.join(" ")));
+        for (i, pose) in self.scanners.iter().enumerate() {
+            obj.push_str(&format!(
+                "o scanner_{}\np {}\n",
+                pose.id,
+                beacon_count + i + 1
+            ));
+        }
+        obj
+    }
+}
+
+// Scanner reports made up from randomly placed beacons and scanners, along with what the alignment should find.
+// Everything is relative to scanner 0, which is at the origin and not rotated.
+struct SyntheticReports {
+    input: String,
+    positions: Vec<Point3>,
+    rotations: Vec<Rotation3>,
+    beacons: Vec<Point3>, // Only those seen by at least one scanner, ordered like unique_beacons
 }
 
-fn unique_beacons(scanners: &[Scanner]) -> Vec<Point3> {
-    let oriented = find_all_positions(scanners);
-    let mut total_beacons: Vec<Point3> = Vec::new();
-    for s in &oriented {
-        total_beacons.extend(s.beacons.iter().map(|p| p + s.position.unwrap()));
+impl SyntheticReports {
+    const SCANNER_RANGE: i32 = 1000;
+
+    fn generate(seed: u64, num_scanners: usize, num_random_beacons: usize, overlap: usize) -> Self {
+        let mut rng = SplitMix64::new(seed);
+        let all_rotations: Vec<Rotation3> = Rotation3::all().collect();
+        let range = Self::SCANNER_RANGE;
+
+        let mut positions = vec![Point3::origin()];
+        let mut rotations = vec![Rotation3::IDENTITY];
+        // Kept in the order they were made so the same seed always gives the same reports
+        let mut beacons: Vec<Point3> = Vec::new();
+
+        // Each new scanner is placed near an existing one and shares some planted beacons with it, so every
+        // scanner can be reached from scanner 0.
+        while positions.len() < num_scanners {
+            // Mostly off to one side, like the puzzle's scanners, so that they don't all crowd together
+            let neighbor = positions[rng.range(0, positions.len() as i32 - 1) as usize];
+            let far = rng.range(range, range * 13 / 10) * if rng.range(0, 1) == 0 { 1 } else { -1 };
+            let near = || (-range * 3 / 10, range * 3 / 10);
+            let offset = match rng.range(0, 2) {
+                0 => Range3 {
+                    x: (far, far),
+                    y: near(),
+                    z: near(),
+                },
+                1 => Range3 {
+                    x: near(),
+                    y: (far, far),
+                    z: near(),
+                },
+                _ => Range3 {
+                    x: near(),
+                    y: near(),
+                    z: (far, far),
+                },
+            };
+            let position = neighbor + rng.point_in(&offset);
+            let shared = Range3 {
+                x: (
+                    neighbor.x.max(position.x) - range,
+                    neighbor.x.min(position.x) + range,
+                ),
+                y: (
+                    neighbor.y.max(position.y) - range,
+                    neighbor.y.min(position.y) + range,
+                ),
+                z: (
+                    neighbor.z.max(position.z) - range,
+                    neighbor.z.min(position.z) + range,
+                ),
+            };
+            let target = beacons.len() + overlap;
+            while beacons.len() < target {
+                let p = rng.point_in(&shared);
+                if beacons.contains(&p) == false {
+                    beacons.push(p);
+                }
+            }
+            positions.push(position);
+            rotations.push(all_rotations[rng.range(0, 23) as usize]);
+        }
+
+        // Sprinkle more beacons over the whole area
+        let area = Point3::get_range(&positions).unwrap();
+        let area = Range3 {
+            x: (area.x.0 - range, area.x.1 + range),
+            y: (area.y.0 - range, area.y.1 + range),
+            z: (area.z.0 - range, area.z.1 + range),
+        };
+        let target = beacons.len() + num_random_beacons;
+        while beacons.len() < target {
+            let p = rng.point_in(&area);
+            if beacons.contains(&p) == false {
+                beacons.push(p);
+            }
+        }
+
+        // Write out what each scanner sees, in its own frame of reference
+        let mut input = String::new();
+        let mut seen: HashSet<Point3> = HashSet::new();
+        for (id, (position, rotation)) in positions.iter().zip(&rotations).enumerate() {
+            input.push_str(&format!("--- scanner {} ---\n", id));
+            let visible = Range3 {
+                x: (position.x - range, position.x + range),
+                y: (position.y - range, position.y + range),
+                z: (position.z - range, position.z + range),
+            };
+            for b in beacons.iter().filter(|b| visible.contains(**b)) {
+                let p = rotation.inverse() * (b - position);
+                input.push_str(&format!("{},{},{}\n", p.x, p.y, p.z));
+                seen.insert(*b);
+            }
+            input.push('\n');
+        }
+
+        let mut beacons: Vec<Point3> = seen.into_iter().collect();
+        beacons.sort_unstable_by(Point3::cmp_xyz);
+
+        Self {
+            input,
+            positions,
+            rotations,
+            beacons,
+        }
     }
-    total_beacons.sort_unstable_by(Point3::cmp_xyz);
-    total_beacons.dedup();
-    total_beacons
+}
+
+pub fn export_beacon_map(input: &str, dir: &Path) -> std::io::Result<()> {
+    let scanners = input_generator(input);
+    let map = match BeaconMap::assemble(&scanners, 12) {
+        Ok(map) => map,
+        Err(ids) => {
+            let msg = format!("Couldn't place scanners: {:?}", ids);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, msg));
+        }
+    };
+    std::fs::write(dir.join("beacons.csv"), map.to_csv())?;
+    std::fs::write(dir.join("beacons.ply"), map.to_ply())?;
+    std::fs::write(dir.join("beacons.obj"), map.to_obj())?;
+    Ok(())
+}
+
+fn unique_beacons(scanners: &[Scanner], overlap_criteria: u32) -> Result<Vec<Point3>, Vec<u32>> {
+    BeaconMap::assemble(scanners, overlap_criteria).map(|map| map.beacons)
 }
 
 fn largest_scanner_distance(scanners: &[Scanner]) -> u32 {
@@ -638,7 +965,7 @@ pub fn input_generator(input: &str) -> Vec<Scanner> {
 
 #[aoc(day19, part1)]
 pub fn part1(input: &[Scanner]) -> usize {
-    let beacons = unique_beacons(input);
+    let beacons = unique_beacons(input, 12).expect("Couldn't place every scanner");
     let num_beacons = beacons.len();
     assert_eq!(num_beacons, 467);
     num_beacons
@@ -646,7 +973,7 @@ pub fn part1(input: &[Scanner]) -> usize {
 
 #[aoc(day19, part2)]
 pub fn part2(input: &[Scanner]) -> u32 {
-    let oriented = find_all_positions(input);
+    let oriented = find_all_positions_indexed(input, 12).expect("Couldn't place every scanner");
     let distance = largest_scanner_distance(&oriented);
     assert_eq!(distance, 12226);
     distance
@@ -655,7 +982,7 @@ pub fn part2(input: &[Scanner]) -> u32 {
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::collections::HashSet;
+    use std::{collections::HashSet, time::Instant};
 
     static EXAMPLE_INPUT1: &str = "\
 --- scanner 0 ---
@@ -852,35 +1179,30 @@ mod test {
     fn test_orient() {
         let scanners = input_generator(EXAMPLE_INPUT2);
 
-        let orientation = Orientation {
-            facing: XYZ::NZ,
-            n: 0,
-        };
-        assert_eq!(scanners[0].orient(orientation).beacons, scanners[0].beacons);
+        assert_eq!(
+            scanners[0].orient(Rotation3::IDENTITY).beacons,
+            scanners[0].beacons
+        );
 
-        let orientation = Orientation {
-            facing: XYZ::Y,
-            n: 2,
-        };
+        let orientation = Rotation3::about_y(2) * Rotation3::about_x(1);
         assert_eq!(scanners[0].orient(orientation).beacons, scanners[1].beacons);
 
-        let orientation = Orientation {
-            facing: XYZ::X,
-            n: 0,
-        };
+        let orientation = Rotation3::about_y(3);
         assert_eq!(scanners[0].orient(orientation).beacons, scanners[2].beacons);
 
-        let orientation = Orientation {
-            facing: XYZ::NX,
-            n: 2,
-        };
+        let orientation = Rotation3::about_x(2) * Rotation3::about_y(1);
         assert_eq!(scanners[0].orient(orientation).beacons, scanners[3].beacons);
 
-        let orientation = Orientation {
-            facing: XYZ::NY,
-            n: 3,
-        };
+        let orientation = Rotation3::about_y(1) * Rotation3::about_x(3);
         assert_eq!(scanners[0].orient(orientation).beacons, scanners[4].beacons);
+
+        // Every view of the example is one of the 24 orientations
+        for scanner in &scanners {
+            let count = Rotation3::all()
+                .filter(|r| scanners[0].orient(*r).beacons == scanner.beacons)
+                .count();
+            assert_eq!(count, 1);
+        }
     }
 
     #[test]
@@ -888,6 +1210,13 @@ mod test {
         let scanners = input_generator(EXAMPLE_INPUT1);
         let res = scanners[0].check_overlap(&scanners[1], 3);
         assert_eq!(res, Some((5, 2, 0).into()));
+
+        // More overlap than needed is still a match
+        let res = scanners[0].check_overlap(&scanners[1], 2);
+        assert_eq!(res, Some((5, 2, 0).into()));
+
+        let res = scanners[0].check_overlap(&scanners[1], 4);
+        assert_eq!(res, None);
     }
 
     #[test]
@@ -896,7 +1225,7 @@ mod test {
 
         let mut oriented: Vec<Scanner> = scanners.clone();
         oriented[0].position = Some(Point3::origin());
-        oriented[0].orientation = Some(Orientation::new());
+        oriented[0].orientation = Some(Rotation3::IDENTITY);
 
         oriented[1] = oriented[0]
             .check_overlap_oriented(&scanners[1], 12)
@@ -919,11 +1248,32 @@ mod test {
         assert_eq!(oriented[3].position, Some((-92, -2380, -20).into()));
     }
 
+    #[test]
+    fn test_chain_transforms() {
+        let scanners = input_generator(EXAMPLE_INPUT3);
+        let oriented = find_all_positions(&scanners, 12).unwrap();
+        let s1 = oriented.iter().find(|s| s.id == 1).unwrap();
+        let s4 = oriented.iter().find(|s| s.id == 4).unwrap();
+
+        // Align scanner 4 against scanner 1 in scanner 1's own frame of reference
+        let mut local = scanners[1].clone();
+        local.position = Some(Point3::origin());
+        let relative = local.check_overlap_oriented(&scanners[4], 12).unwrap();
+
+        // Chaining 4 -> 1 -> origin gives the same pose as aligning against the map directly
+        let r1 = s1.orientation.unwrap();
+        let r4 = r1 * relative.orientation.unwrap();
+        let p4 = s1.position.unwrap() + r1 * relative.position.unwrap();
+        assert_eq!(Some(r4), s4.orientation);
+        assert_eq!(Some(p4), s4.position);
+        assert_eq!(r1.inverse() * r4, relative.orientation.unwrap());
+    }
+
     #[test]
     fn test_find_all_positions() {
         let scanners = input_generator(EXAMPLE_INPUT3);
 
-        let oriented = find_all_positions(&scanners);
+        let oriented = find_all_positions(&scanners, 12).unwrap();
         let positions: HashSet<Point3> = oriented.into_iter().filter_map(|s| s.position).collect();
         assert_eq!(
             positions,
@@ -939,10 +1289,102 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_find_all_positions_unplaceable() {
+        let mut scanners = input_generator(EXAMPLE_INPUT3);
+        let mut lonely = input_generator(EXAMPLE_INPUT1);
+        lonely[0].id = 5;
+        lonely[1].id = 6;
+        scanners.extend(lonely);
+
+        assert_eq!(find_all_positions(&scanners, 12), Err(vec![5, 6]));
+        assert_eq!(unique_beacons(&scanners, 12), Err(vec![5, 6]));
+        assert_eq!(find_all_positions(&[], 12), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_find_all_positions_criteria() {
+        let scanners = input_generator(EXAMPLE_INPUT1);
+        let oriented = find_all_positions(&scanners, 3).unwrap();
+        assert_eq!(oriented[1].position, Some((5, 2, 0).into()));
+
+        // Demanding more overlap than the example provides means nothing can be placed
+        let scanners = input_generator(EXAMPLE_INPUT3);
+        assert_eq!(find_all_positions(&scanners, 30), Err(vec![1, 2, 3, 4]));
+    }
+
+    // Timing only means much with optimizations: cargo test --release time_find_all_positions -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn time_find_all_positions() {
+        let scanners = input_generator(include_str!("../input/2021/day19.txt"));
+
+        let start = Instant::now();
+        let brute_force = find_all_positions(&scanners, 12).unwrap();
+        let brute_force_time = start.elapsed();
+
+        let start = Instant::now();
+        let indexed = find_all_positions_indexed(&scanners, 12).unwrap();
+        let indexed_time = start.elapsed();
+
+        println!(
+            "Brute force: {:?}, indexed: {:?}",
+            brute_force_time, indexed_time
+        );
+        assert_eq!(brute_force.len(), indexed.len());
+    }
+
+    #[test]
+    fn test_find_all_positions_indexed() {
+        let scanners = input_generator(EXAMPLE_INPUT3);
+
+        let mut brute_force = find_all_positions(&scanners, 12).unwrap();
+        let mut indexed = find_all_positions_indexed(&scanners, 12).unwrap();
+
+        // Same positions, orientations and beacons for every scanner
+        brute_force.sort_unstable_by_key(|s| s.id);
+        indexed.sort_unstable_by_key(|s| s.id);
+        assert_eq!(brute_force.len(), indexed.len());
+        for (a, b) in brute_force.iter().zip(&indexed) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.orientation, b.orientation);
+            assert_eq!(a.beacons, b.beacons);
+        }
+
+        let mut scanners = scanners;
+        let mut lonely = input_generator(EXAMPLE_INPUT1);
+        lonely[0].id = 5;
+        lonely[1].id = 6;
+        scanners.extend(lonely);
+        assert_eq!(find_all_positions_indexed(&scanners, 12), Err(vec![5, 6]));
+        assert_eq!(find_all_positions_indexed(&[], 12), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_fingerprints() {
+        let scanners = input_generator(EXAMPLE_INPUT2);
+
+        // Every view of the same beacons has the same triangles
+        let mut expected: Vec<Fingerprint> = scanners[0]
+            .fingerprints()
+            .into_iter()
+            .map(|(f, _)| f)
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(expected.len(), 18); // 6 choose 3, less the isosceles ones
+        for scanner in &scanners[1..] {
+            let mut fingerprints: Vec<Fingerprint> =
+                scanner.fingerprints().into_iter().map(|(f, _)| f).collect();
+            fingerprints.sort_unstable();
+            assert_eq!(fingerprints, expected);
+        }
+    }
+
     #[test]
     fn test_unique_beacons() {
         let scanners = input_generator(EXAMPLE_INPUT3);
-        let beacons = unique_beacons(&scanners);
+        let beacons = unique_beacons(&scanners, 12).unwrap();
         assert_eq!(beacons.len(), 79);
         assert_eq!(
             beacons,
@@ -1030,10 +1472,105 @@ mod test {
         );
     }
 
+    fn check_synthetic(reports: &SyntheticReports, oriented: &[Scanner]) {
+        assert_eq!(oriented.len(), reports.positions.len());
+        for scanner in oriented {
+            let id = scanner.id as usize;
+            assert_eq!(scanner.position, Some(reports.positions[id]));
+            assert_eq!(scanner.orientation, Some(reports.rotations[id]));
+        }
+    }
+
+    #[test]
+    fn test_synthetic_reports() {
+        let reports = SyntheticReports::generate(1, 6, 40, 12);
+        let scanners = input_generator(&reports.input);
+        assert_eq!(scanners.len(), 6);
+        assert_eq!(scanners[0].id, 0);
+
+        // Scanner 0 sees things as they are
+        let seen: HashSet<Point3> = scanners[0].beacons.iter().copied().collect();
+        assert!(seen.iter().all(|p| reports.beacons.contains(p)));
+
+        // The same seed gives the same reports
+        assert_eq!(
+            SyntheticReports::generate(1, 6, 40, 12).input,
+            reports.input
+        );
+        assert_ne!(
+            SyntheticReports::generate(2, 6, 40, 12).input,
+            reports.input
+        );
+    }
+
+    #[test]
+    fn test_fuzz_find_all_positions() {
+        for seed in 0..3 {
+            let reports = SyntheticReports::generate(seed, 5, 20, 12);
+            let scanners = input_generator(&reports.input);
+            check_synthetic(&reports, &find_all_positions(&scanners, 12).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fuzz_find_all_positions_indexed() {
+        for seed in 0..10 {
+            let reports = SyntheticReports::generate(seed, 8, 30, 12);
+            let scanners = input_generator(&reports.input);
+            check_synthetic(
+                &reports,
+                &find_all_positions_indexed(&scanners, 12).unwrap(),
+            );
+            assert_eq!(unique_beacons(&scanners, 12).unwrap(), reports.beacons);
+        }
+    }
+
+    #[test]
+    fn test_beacon_map() {
+        let scanners = input_generator(EXAMPLE_INPUT3);
+        let map = BeaconMap::assemble(&scanners, 12).unwrap();
+        assert_eq!(map.beacons.len(), 79);
+        let ids: Vec<u32> = map.scanners.iter().map(|pose| pose.id).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+        assert_eq!(map.scanners[2].position, (1105, -1205, 1229).into());
+
+        // Each scanner's rotation takes its own report onto the map
+        for (pose, scanner) in map.scanners.iter().zip(&scanners) {
+            for p in &scanner.beacons {
+                let absolute = pose.rotation.apply(*p) + pose.position;
+                assert!(map.beacons.contains(&absolute));
+            }
+        }
+
+        let csv = map.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("kind,id,x,y,z,r00,r01,r02,r10,r11,r12,r20,r21,r22")
+        );
+        assert_eq!(lines.next(), Some("scanner,0,0,0,0,1,0,0,0,1,0,0,0,1"));
+        assert_eq!(csv.lines().count(), 1 + 5 + 79);
+        assert!(csv.contains("\nbeacon,,-892,524,684,,,,,,,,,\n"));
+
+        let ply = map.to_ply();
+        assert!(ply.starts_with("ply\nformat ascii 1.0\nelement vertex 84\n"));
+        assert_eq!(
+            ply.lines().skip_while(|l| *l != "end_header").count(),
+            1 + 84
+        );
+        assert!(ply.contains("\n-892 524 684 255 255 255\n"));
+        assert!(ply.ends_with("-20 -1133 1061 255 0 0\n"));
+
+        let obj = map.to_obj();
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 84);
+        assert!(obj.starts_with("v -892 524 684\n"));
+        assert!(obj.ends_with("o scanner_4\np 84\n"));
+    }
+
     #[test]
     fn test_largest_scanner_distance() {
         let scanners = input_generator(EXAMPLE_INPUT3);
-        let oriented = find_all_positions(&scanners);
+        let oriented = find_all_positions(&scanners, 12).unwrap();
         let distance = largest_scanner_distance(&oriented);
         assert_eq!(distance, 3621);
     }
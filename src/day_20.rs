@@ -210,116 +210,301 @@
     Start again with the original input image and apply the image enhancement algorithm 50 times. How many pixels are lit in the resulting image?
 */
 
-use crate::common::{Point2, Range2};
-use std::collections::HashSet;
+use crate::common::{Boundary, Grid, Neighborhood, Point2, Rule};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LitPixels {
+    Finite(usize),
+    Infinite,
+}
+
+// The infinite background steps through its own little state machine: a dark background becomes algo[0] and a lit
+// one becomes algo[511].
+fn next_background(algo: &[bool], background: bool) -> bool {
+    algo[if background == true { 511 } else { 0 }]
+}
+
+// The 3x3 window centered on a pixel, read left to right and top to bottom as the bits of the lookup
+fn window_as_int(window: &[bool]) -> usize {
+    window
+        .iter()
+        .fold(0, |n, lit| n << 1 | if *lit == true { 1 } else { 0 })
+}
+
+struct Enhance<'a> {
+    algo: &'a [bool],
+}
+
+impl Rule for Enhance<'_> {
+    type Cell = bool;
+
+    fn neighborhood(&self) -> Neighborhood {
+        let mut window = Vec::new();
+        for y in [-1, 0, 1] {
+            for x in [-1, 0, 1] {
+                window.push((x, y).into());
+            }
+        }
+        Neighborhood::Custom(window)
+    }
+
+    fn next(&self, _cell: &bool, neighbors: &[bool]) -> bool {
+        self.algo[window_as_int(neighbors)]
+    }
+}
 
 #[derive(Clone)]
 pub struct Image {
     algo: Vec<bool>,
-    data: HashSet<Point2>,
-    range: Range2,
-    infinity_is_lit: bool,
-    infinity_flips: bool,
+    grid: Grid<bool>,
 }
 
 impl Image {
     fn from_string(input: &str) -> Self {
         let mut lines = input.lines();
         let algo: Vec<bool> = lines.next().unwrap().chars().map(|c| c == '#').collect();
+        assert_eq!(algo.len(), 512);
 
         let _ = lines.next();
 
-        let mut data = HashSet::new();
-        for (y, line) in lines.enumerate() {
-            for (x, c) in line.chars().enumerate() {
-                if c == '#' {
-                    data.insert(Point2 {
-                        x: x as i32,
-                        y: y as i32,
-                    });
-                }
-            }
-        }
-
-        let range = Point2::get_range(&data).unwrap();
-
-        let infinity_flips = match (algo[0], algo.last().unwrap()) {
-            (false, false) => false,
-            (false, true) => false,
-            (true, false) => true,
-            (true, true) => false,
-        };
+        let rows: Vec<Vec<bool>> = lines
+            .map(|line| line.chars().map(|c| c == '#').collect())
+            .collect();
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let rows = rows
+            .into_iter()
+            .map(|mut r| {
+                r.resize(width, false);
+                r
+            })
+            .collect();
 
         Self {
             algo,
-            data,
-            range,
-            infinity_is_lit: false,
-            infinity_flips,
+            grid: Grid::from_rows(rows, Boundary::Infinite(false)),
         }
     }
 
-    fn value(&self, p: &Point2) -> bool {
-        if self.range.contains(*p) == true {
-            self.data.get(p).is_some()
-        } else if self.infinity_is_lit == true {
-            true
-        } else {
-            false
-        }
+    fn infinity_is_lit(&self) -> bool {
+        self.grid.boundary() == &Boundary::Infinite(true)
     }
 
     fn neighbors_as_int(&self, p: &Point2) -> usize {
-        let mut n = 0;
-        for y in [-1, 0, 1] {
-            for x in [-1, 0, 1] {
-                n <<= 1;
-                n |= if self.value(&(p.x + x, p.y + y).into()) == true {
-                    1
-                } else {
-                    0
-                };
-            }
-        }
-        n
+        let window: Vec<bool> = Enhance { algo: &self.algo }
+            .neighborhood()
+            .offsets()
+            .iter()
+            .map(|o| *self.grid.get(p + o))
+            .collect();
+        window_as_int(&window)
     }
 
     fn enhance(&mut self, n: u32) {
-        let mut new_pixels: Vec<Point2> = Vec::new();
+        let rule = Enhance { algo: &self.algo };
         for _ in 0..n {
-            new_pixels.clear();
-
-            for y in self.range.y.0 - 1..=self.range.y.1 + 1 {
-                for x in self.range.x.0 - 1..=self.range.x.1 + 1 {
-                    let pixel = Point2 { x, y };
-                    let lookup = self.neighbors_as_int(&pixel);
-                    assert!(lookup < self.algo.len());
-                    if self.algo[lookup] == true {
-                        new_pixels.push(pixel);
+            self.grid.step(&rule);
+        }
+    }
+
+    fn count_lit_pixels(&self) -> LitPixels {
+        if self.infinity_is_lit() == true {
+            LitPixels::Infinite
+        } else {
+            LitPixels::Finite(self.grid.iter().filter(|(_, lit)| **lit == true).count())
+        }
+    }
+}
+
+impl std::fmt::Display for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Only show the area that differs from the background
+        let background = self.infinity_is_lit();
+        let differs: Vec<Point2> = self
+            .grid
+            .iter()
+            .filter(|(_, lit)| **lit != background)
+            .map(|(p, _)| p)
+            .collect();
+        if let Some(range) = Point2::get_range(&differs) {
+            for y in range.y.0..=range.y.1 {
+                for x in range.x.0..=range.x.1 {
+                    if *self.grid.get((x, y).into()) == true {
+                        write!(f, "#")?;
+                    } else {
+                        write!(f, ".")?;
                     }
                 }
+                writeln!(f)?;
             }
+        }
+        Ok(())
+    }
+}
+
+// The same image stored as a dense bitmap covering just the area that differs from the infinite background. It steps
+// with a sliding window rather than through the automaton, and is what the PBM/PGM export works from.
+#[derive(Clone)]
+pub struct DenseImage {
+    algo: Vec<bool>,
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>, // Row-major
+    background: bool,
+}
+
+impl DenseImage {
+    fn from_string(input: &str) -> Self {
+        Self::from(&Image::from_string(input))
+    }
 
-            self.data.clear();
-            self.data.extend(new_pixels.drain(..));
-            self.range = Point2::get_range(&self.data).unwrap();
+    fn step(&mut self) {
+        // Surround the image with two pixels of background so the window never has to check its bounds
+        let padded_width = self.width + 4;
+        let padded_height = self.height + 4;
+        let mut padded = vec![self.background; padded_width * padded_height];
+        for (y, row) in self
+            .pixels
+            .chunks(self.width.max(1))
+            .take(self.height)
+            .enumerate()
+        {
+            let start = (y + 2) * padded_width + 2;
+            padded[start..start + self.width].copy_from_slice(row);
+        }
 
-            if self.infinity_flips == true {
-                self.infinity_is_lit = !self.infinity_is_lit;
+        // The output is one pixel bigger on every side. Output (x, y) is centered on padded (x + 1, y + 1).
+        let width = self.width + 2;
+        let height = self.height + 2;
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let rows = [y, y + 1, y + 2].map(|r| &padded[r * padded_width..(r + 1) * padded_width]);
+            let column = |x: usize| {
+                (rows[0][x] as usize) << 6 | (rows[1][x] as usize) << 3 | rows[2][x] as usize
+            };
+
+            // Slide the 3x3 window along the row, bringing in one new column of pixels each time
+            let mut lookup = column(0) << 1 | column(1);
+            for x in 0..width {
+                lookup = ((lookup << 1) & 0b110_110_110) | column(x + 2);
+                pixels.push(self.algo[lookup]);
             }
         }
+
+        self.width = width;
+        self.height = height;
+        self.pixels = pixels;
+        self.background = next_background(&self.algo, self.background);
+        self.trim();
     }
 
-    fn count_lit_pixels(&self) -> usize {
-        self.data.len()
+    fn trim(&mut self) {
+        // Drop any rows and columns at the edges that look just like the background
+        let row_is_bg = |img: &Self, y: usize| {
+            (0..img.width).all(|x| img.pixels[y * img.width + x] == img.background)
+        };
+        let col_is_bg = |img: &Self, x: usize| {
+            (0..img.height).all(|y| img.pixels[y * img.width + x] == img.background)
+        };
+
+        let top = (0..self.height).find(|y| row_is_bg(self, *y) == false);
+        let top = match top {
+            Some(top) => top,
+            None => {
+                self.width = 0;
+                self.height = 0;
+                self.pixels.clear();
+                return;
+            }
+        };
+        let bottom = (0..self.height)
+            .rev()
+            .find(|y| row_is_bg(self, *y) == false)
+            .unwrap();
+        let left = (0..self.width)
+            .find(|x| col_is_bg(self, *x) == false)
+            .unwrap();
+        let right = (0..self.width)
+            .rev()
+            .find(|x| col_is_bg(self, *x) == false)
+            .unwrap();
+
+        let width = right - left + 1;
+        let height = bottom - top + 1;
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in top..=bottom {
+            pixels.extend_from_slice(&self.pixels[y * self.width + left..=y * self.width + right]);
+        }
+        self.width = width;
+        self.height = height;
+        self.pixels = pixels;
+    }
+
+    fn enhance(&mut self, n: u32) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    fn count_lit_pixels(&self) -> LitPixels {
+        if self.background == true {
+            LitPixels::Infinite
+        } else {
+            LitPixels::Finite(self.pixels.iter().filter(|p| **p == true).count())
+        }
+    }
+
+    fn to_pbm(&self) -> String {
+        // Plain PBM, where 1 is black. Lit pixels are drawn black like the '#' in the puzzle.
+        let mut pbm = format!("P1\n{} {}\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            let values: Vec<&str> = row
+                .iter()
+                .map(|p| if *p == true { "1" } else { "0" })
+                .collect();
+            pbm.push_str(&values.join(" "));
+            pbm.push('\n');
+        }
+        pbm
+    }
+
+    fn to_pgm(&self) -> String {
+        // Plain PGM, where lit pixels are white
+        let mut pgm = format!("P2\n{} {}\n255\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            let values: Vec<&str> = row
+                .iter()
+                .map(|p| if *p == true { "255" } else { "0" })
+                .collect();
+            pgm.push_str(&values.join(" "));
+            pgm.push('\n');
+        }
+        pgm
     }
 }
 
-impl std::fmt::Display for Image {
+impl From<&Image> for DenseImage {
+    fn from(image: &Image) -> Self {
+        let mut dense = Self {
+            algo: image.algo.clone(),
+            width: image.grid.width(),
+            height: image.grid.height(),
+            pixels: image.grid.rows().flatten().copied().collect(),
+            background: image.infinity_is_lit(),
+        };
+        dense.trim();
+        dense
+    }
+}
+
+impl std::fmt::Display for DenseImage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in self.range.y.0..=self.range.y.1 {
-            for x in self.range.x.0..=self.range.x.1 {
-                if self.value(&(x, y).into()) == true {
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            for p in row {
+                if *p == true {
                     write!(f, "#")?;
                 } else {
                     write!(f, ".")?;
@@ -340,7 +525,10 @@ pub fn input_generator(input: &str) -> Image {
 pub fn part1(input: &Image) -> usize {
     let mut image = input.clone();
     image.enhance(2);
-    let lit = image.count_lit_pixels();
+    let lit = match image.count_lit_pixels() {
+        LitPixels::Finite(lit) => lit,
+        LitPixels::Infinite => panic!("Infinitely many pixels are lit"),
+    };
     assert_eq!(lit, 5563);
     lit
 }
@@ -349,7 +537,10 @@ pub fn part1(input: &Image) -> usize {
 pub fn part2(input: &Image) -> usize {
     let mut image = input.clone();
     image.enhance(50);
-    let lit = image.count_lit_pixels();
+    let lit = match image.count_lit_pixels() {
+        LitPixels::Finite(lit) => lit,
+        LitPixels::Infinite => panic!("Infinitely many pixels are lit"),
+    };
     assert_eq!(lit, 19743);
     lit
 }
@@ -370,7 +561,7 @@ mod test {
 
     #[test]
     fn test_neighbors_as_int() {
-        let image = input_generator(EXAMPLE_INPUT);
+        let image = Image::from_string(EXAMPLE_INPUT);
         let lookup = image.neighbors_as_int(&(2, 2).into());
         assert_eq!(lookup, 34);
         assert_eq!(image.algo[lookup], true);
@@ -378,7 +569,7 @@ mod test {
 
     #[test]
     fn test_enhance() {
-        let mut image = input_generator(EXAMPLE_INPUT);
+        let mut image = Image::from_string(EXAMPLE_INPUT);
 
         image.enhance(1);
         assert_eq!(
@@ -411,12 +602,129 @@ mod test {
 
     #[test]
     fn test_count_lit_pixels() {
-        let mut image = input_generator(EXAMPLE_INPUT);
+        let mut image = Image::from_string(EXAMPLE_INPUT);
         image.enhance(2);
-        assert_eq!(image.count_lit_pixels(), 35);
+        assert_eq!(image.count_lit_pixels(), LitPixels::Finite(35));
 
-        let mut image = input_generator(EXAMPLE_INPUT);
+        let mut image = Image::from_string(EXAMPLE_INPUT);
         image.enhance(50);
-        assert_eq!(image.count_lit_pixels(), 3351);
+        assert_eq!(image.count_lit_pixels(), LitPixels::Finite(3351));
+    }
+
+    #[test]
+    fn test_dense_matches_sparse() {
+        let mut sparse = Image::from_string(EXAMPLE_INPUT);
+        let mut dense = DenseImage::from_string(EXAMPLE_INPUT);
+        assert_eq!(dense.to_string(), sparse.to_string());
+        for _ in 0..10 {
+            sparse.enhance(1);
+            dense.enhance(1);
+            assert_eq!(dense.to_string(), sparse.to_string());
+            assert_eq!(dense.count_lit_pixels(), sparse.count_lit_pixels());
+        }
+
+        let mut dense = DenseImage::from_string(EXAMPLE_INPUT);
+        dense.enhance(50);
+        assert_eq!(dense.count_lit_pixels(), LitPixels::Finite(3351));
+    }
+
+    #[test]
+    fn test_dense_flashing_background() {
+        // Empty becomes lit and full becomes dark, so the background alternates. A lone lit pixel stays lit.
+        let mut algo = vec!['.'; 512];
+        algo[0] = '#';
+        algo[0b000_010_000] = '#';
+        let input = format!("{}\n\n...\n.#.\n...", algo.iter().collect::<String>());
+        let mut image = DenseImage::from_string(&input);
+        assert_eq!(image.count_lit_pixels(), LitPixels::Finite(1));
+
+        image.enhance(1);
+        assert_eq!(image.background, true);
+        assert_eq!(image.to_string(), "...\n.#.\n...\n");
+
+        image.enhance(1);
+        assert_eq!(image.background, false);
+        assert_eq!(image.to_string(), "#\n");
+
+        image.enhance(1001);
+        assert_eq!(image.background, true);
+        assert_eq!(image.to_string(), "...\n.#.\n...\n");
+    }
+
+    #[test]
+    fn test_background() {
+        let algo: Vec<bool> = EXAMPLE_INPUT
+            .lines()
+            .next()
+            .unwrap()
+            .chars()
+            .map(|c| c == '#')
+            .collect();
+        assert_eq!(next_background(&algo, false), false);
+        assert_eq!(next_background(&algo, true), true);
+
+        // Every combination of what happens to a dark and a lit background
+        for (first, last) in [('.', '.'), ('.', '#'), ('#', '.'), ('#', '#')] {
+            let mut input = EXAMPLE_INPUT.to_string();
+            input.replace_range(0..1, &first.to_string());
+            input.replace_range(511..512, &last.to_string());
+
+            let mut sparse = Image::from_string(&input);
+            let mut dense = DenseImage::from_string(&input);
+            let mut background = false;
+            for _ in 0..6 {
+                sparse.enhance(1);
+                dense.enhance(1);
+                background = if background == true {
+                    last == '#'
+                } else {
+                    first == '#'
+                };
+
+                assert_eq!(sparse.infinity_is_lit(), background);
+                assert_eq!(dense.background, background);
+                assert_eq!(sparse.to_string(), dense.to_string());
+                assert_eq!(sparse.count_lit_pixels(), dense.count_lit_pixels());
+                if background == true {
+                    assert_eq!(sparse.count_lit_pixels(), LitPixels::Infinite);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_lit_algorithm() {
+        // Previously treated as never flipping, but everything is lit after one step and stays that way
+        let input = format!("{}\n\n#..\n...\n..#", "#".repeat(512));
+        let mut image = Image::from_string(&input);
+        assert_eq!(image.count_lit_pixels(), LitPixels::Finite(2));
+        image.enhance(1);
+        assert_eq!(image.count_lit_pixels(), LitPixels::Infinite);
+        assert_eq!(image.to_string(), "");
+        image.enhance(5);
+        assert_eq!(image.count_lit_pixels(), LitPixels::Infinite);
+    }
+
+    #[test]
+    fn test_dense_export() {
+        let image = DenseImage::from_string(EXAMPLE_INPUT);
+        assert_eq!(
+            image.to_pbm(),
+            "\
+P1
+5 5
+1 0 0 1 0
+1 0 0 0 0
+1 1 0 0 1
+0 0 1 0 0
+0 0 1 1 1
+"
+        );
+
+        let mut image = image;
+        image.enhance(2);
+        let pgm = image.to_pgm();
+        assert!(pgm.starts_with("P2\n9 9\n255\n0 0 0 0 0 0 0 255 0\n"));
+        assert_eq!(pgm.lines().count(), 3 + 9);
     }
 }
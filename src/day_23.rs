@@ -312,6 +312,8 @@ use crate::common::Point2;
 use std::{
     cmp::Reverse,
     collections::{BinaryHeap, HashMap},
+    io::{BufRead, Write},
+    time::{Duration, Instant},
 };
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -360,14 +362,102 @@ impl Amphipod {
         }
     }
 
+    fn to_bits(amph: Option<Self>) -> u128 {
+        match amph {
+            None => 0,
+            Some(Self::A) => 1,
+            Some(Self::B) => 2,
+            Some(Self::C) => 3,
+            Some(Self::D) => 4,
+        }
+    }
+
+    fn from_bits(bits: u128) -> Option<Self> {
+        match bits {
+            0 => None,
+            1 => Some(Self::A),
+            2 => Some(Self::B),
+            3 => Some(Self::C),
+            4 => Some(Self::D),
+            x => panic!("Invalid amphipod bits: {}", x),
+        }
+    }
+
     const ALL: [Self; 4] = [Self::A, Self::B, Self::C, Self::D];
     fn iter() -> impl Iterator<Item = Self> {
         Self::ALL.into_iter()
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum Location {
+    Hallway(usize),
+    Room(Amphipod, usize),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hallway(idx) => write!(f, "hallway {}", idx),
+            Self::Room(room, idx) => write!(f, "room {} position {}", room.to_char(), idx),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Move {
+    amph: Amphipod,
+    from: Location,
+    to: Location,
+    energy: u32,
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} moves from {} to {}, using {} energy",
+            self.amph.to_char(),
+            self.from,
+            self.to,
+            self.energy
+        )
+    }
+}
+
 #[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct State<const N: usize>(u32, Burrow<N>);
+struct State<const N: usize>(u32, PackedBurrow<N>);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct SearchStats {
+    expanded: usize,
+    peak_queue: usize,
+    elapsed: Duration,
+}
+
+impl std::fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} states expanded, peak queue size {}, took {:?}",
+            self.expanded, self.peak_queue, self.elapsed
+        )
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Solution<const N: usize> {
+    energy: u32,
+    start: Burrow<N>,
+    steps: Vec<(Move, Burrow<N>)>,
+    stats: SearchStats,
+}
+
+impl<const N: usize> Solution<N> {
+    fn moves(&self) -> impl Iterator<Item = &Move> {
+        self.steps.iter().map(|(m, _)| m)
+    }
+}
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 struct Burrow<const N: usize> {
@@ -375,6 +465,48 @@ struct Burrow<const N: usize> {
     rooms: [[Option<Amphipod>; N]; 4],
 }
 
+// Each cell of the burrow takes 3 bits: the hallway first, then each room from top to bottom.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct PackedBurrow<const N: usize>(u128);
+
+impl<const N: usize> PackedBurrow<N> {
+    const CELL_BITS: usize = 3;
+    const CELL_MASK: u128 = 0b111;
+}
+
+impl<const N: usize> From<Burrow<N>> for PackedBurrow<N> {
+    fn from(burrow: Burrow<N>) -> Self {
+        assert!((burrow.hallway.len() + 4 * N) * Self::CELL_BITS <= 128);
+        let cells = burrow.hallway.iter().chain(burrow.rooms.iter().flatten());
+        let packed = cells.enumerate().fold(0, |acc, (i, cell)| {
+            acc | (Amphipod::to_bits(*cell) << (i * Self::CELL_BITS))
+        });
+        Self(packed)
+    }
+}
+
+impl<const N: usize> From<PackedBurrow<N>> for Burrow<N> {
+    fn from(packed: PackedBurrow<N>) -> Self {
+        let cell = |i: usize| {
+            Amphipod::from_bits(
+                (packed.0 >> (i * PackedBurrow::<N>::CELL_BITS)) & PackedBurrow::<N>::CELL_MASK,
+            )
+        };
+
+        let mut burrow = Self::new();
+        for (i, h) in burrow.hallway.iter_mut().enumerate() {
+            *h = cell(i);
+        }
+        let offset = burrow.hallway.len();
+        for (r, room) in burrow.rooms.iter_mut().enumerate() {
+            for (i, a) in room.iter_mut().enumerate() {
+                *a = cell(offset + r * N + i);
+            }
+        }
+        burrow
+    }
+}
+
 impl<const N: usize> Burrow<N> {
     const END_STATE: Self = Self {
         hallway: [None; 7],
@@ -411,7 +543,7 @@ impl<const N: usize> Burrow<N> {
                     'A'..='D' => {
                         let amph = Some(Amphipod::from_char(c));
                         if y == 1 {
-                            let idx = Self::x_to_hall_idx(x as i32);
+                            let idx = Self::x_to_hall_idx(x as i32 - 1); // Hallway x coordinates start after the wall
                             burrow.hallway[idx] = amph;
                         } else if y >= 2 {
                             burrow.rooms[room][y - 2] = amph;
@@ -479,7 +611,7 @@ impl<const N: usize> Burrow<N> {
         }
     }
 
-    fn rooms_to_hallway_moves(&self) -> Vec<State<N>> {
+    fn rooms_to_hallway_moves(&self) -> Vec<(Move, Burrow<N>)> {
         let mut states = Vec::new();
         for room in Amphipod::iter() {
             if self.is_room_ready(room) == false {
@@ -505,7 +637,13 @@ impl<const N: usize> Burrow<N> {
                             let hall_p = Self::hall_idx_to_p(hall_idx);
                             let room_p = Self::room_idx_to_p(room, room_idx);
                             let cost = Point2::manhattan(hall_p, room_p) * amph.cost();
-                            states.push(State(cost, next));
+                            let mv = Move {
+                                amph,
+                                from: Location::Room(room, room_idx),
+                                to: Location::Hallway(hall_idx),
+                                energy: cost,
+                            };
+                            states.push((mv, next));
                         }
                     }
                 }
@@ -514,7 +652,7 @@ impl<const N: usize> Burrow<N> {
         states
     }
 
-    fn hallway_to_rooms_moves(&self) -> Vec<State<N>> {
+    fn hallway_to_rooms_moves(&self) -> Vec<(Move, Burrow<N>)> {
         let mut states = Vec::new();
         for (hall_idx, h) in self.hallway.into_iter().enumerate() {
             if let Some(amph) = h {
@@ -534,7 +672,13 @@ impl<const N: usize> Burrow<N> {
                     let hall_p = Self::hall_idx_to_p(hall_idx);
                     let room_p = Self::room_idx_to_p(amph, room_idx);
                     let cost = Point2::manhattan(hall_p, room_p) * amph.cost();
-                    states.push(State(cost, next));
+                    let mv = Move {
+                        amph,
+                        from: Location::Hallway(hall_idx),
+                        to: Location::Room(amph, room_idx),
+                        energy: cost,
+                    };
+                    states.push((mv, next));
                 }
             }
         }
@@ -581,22 +725,77 @@ impl<const N: usize> Burrow<N> {
         None
     }
 
-    fn organize(&self) -> u32 {
-        let mut best_states: HashMap<Burrow<N>, u32> = HashMap::new();
-        best_states.insert(*self, 0);
+    fn min_remaining_energy(&self) -> u32 {
+        // A lower bound on the energy left to spend: every amphipod that still has to move walks straight to the top
+        // of its room, as if nothing else were in the way.
+        let mut energy = 0;
+        for (hall_idx, h) in self.hallway.iter().enumerate() {
+            if let Some(amph) = h {
+                let hall_p = Self::hall_idx_to_p(hall_idx);
+                let room_p = Self::room_idx_to_p(*amph, 0);
+                energy += Point2::manhattan(hall_p, room_p) * amph.cost();
+            }
+        }
+
+        for room in Amphipod::iter() {
+            let contents = &self.rooms[room.room_idx()];
+            for (room_idx, a) in contents.iter().enumerate() {
+                if let Some(amph) = *a {
+                    let room_p = Self::room_idx_to_p(room, room_idx);
+                    let steps = if amph != room {
+                        // Up to the hallway, across to its room and down into it
+                        let target_p = Self::room_idx_to_p(amph, 0);
+                        room_p.y as u32 + (room_p.x - target_p.x).unsigned_abs() + 1
+                    } else if contents[room_idx + 1..].iter().any(|b| *b != Some(room)) == true {
+                        // In the right room but blocking someone below, so it has to step aside and come back
+                        room_p.y as u32 + 2 + 1
+                    } else {
+                        0
+                    };
+                    energy += steps * amph.cost();
+                }
+            }
+        }
+        energy
+    }
+
+    fn organize(&self) -> Option<Solution<N>> {
+        self.search(|_| 0)
+    }
+
+    fn organize_astar(&self) -> Option<Solution<N>> {
+        self.search(Self::min_remaining_energy)
+    }
+
+    fn search(&self, heuristic: impl Fn(&Self) -> u32) -> Option<Solution<N>> {
+        let start_time = Instant::now();
+        let mut stats = SearchStats::default();
+
+        let start: PackedBurrow<N> = (*self).into();
+        let end: PackedBurrow<N> = Burrow::END_STATE.into();
 
+        let mut best_states: HashMap<PackedBurrow<N>, u32> = HashMap::new();
+        best_states.insert(start, 0);
+        let mut predecessors: HashMap<PackedBurrow<N>, (Move, PackedBurrow<N>)> = HashMap::new();
+
+        // States are prioritized by their cost so far plus the heuristic's estimate of the cost to finish
         let mut state_queue: BinaryHeap<Reverse<State<N>>> = BinaryHeap::new();
-        state_queue.push(Reverse(State(0, *self)));
-        while let Some(Reverse(State(curr_cost, curr_state))) = state_queue.pop() {
-            //println!("{}", curr_state);
-            if curr_state == Burrow::END_STATE {
+        state_queue.push(Reverse(State(heuristic(self), start)));
+        while let Some(Reverse(State(priority, curr_packed))) = state_queue.pop() {
+            if curr_packed == end {
                 continue; // This is the end state, no need to search further
             }
-            if let Some(best_cost) = best_states.get(&Burrow::END_STATE) {
-                if curr_cost >= *best_cost {
-                    continue; // This is already worse than another result we've already found so skip it
+            if let Some(best_cost) = best_states.get(&end) {
+                if priority >= *best_cost {
+                    continue; // This can't do better than another result we've already found so skip it
                 }
             }
+            let curr_cost = best_states[&curr_packed];
+            let curr_state = Burrow::from(curr_packed);
+            if priority > curr_cost + heuristic(&curr_state) {
+                continue; // A cheaper way to this state was found after this one was queued
+            }
+            stats.expanded += 1;
 
             /*
                 If there are moves that go to rooms, only consider those options. This improves the efficiency of the
@@ -610,19 +809,55 @@ impl<const N: usize> Burrow<N> {
                 moves.extend(hallway_moves);
             }
 
-            for State(cost, next_burrow) in moves {
-                let next_cost = curr_cost + cost;
-                if let Some(best_cost) = best_states.get(&next_burrow) {
+            for (mv, next_burrow) in moves {
+                let next_cost = curr_cost + mv.energy;
+                let next_packed = next_burrow.into();
+                if let Some(best_cost) = best_states.get(&next_packed) {
                     if next_cost >= *best_cost {
                         continue; // Next isn't better so don't bother with it
                     }
                 }
-                state_queue.push(Reverse(State(next_cost, next_burrow)));
-                best_states.insert(next_burrow, next_cost);
+                let next_priority = next_cost + heuristic(&next_burrow);
+                state_queue.push(Reverse(State(next_priority, next_packed)));
+                best_states.insert(next_packed, next_cost);
+                predecessors.insert(next_packed, (mv, curr_packed));
             }
+            stats.peak_queue = stats.peak_queue.max(state_queue.len());
         }
 
-        best_states[&Burrow::END_STATE]
+        // If the end state was never reached the amphipods are stuck
+        let energy = *best_states.get(&end)?;
+
+        // Walk the predecessors back from the end state to recover the moves taken
+        let mut steps = Vec::new();
+        let mut curr = end;
+        while curr != start {
+            let (mv, prev) = predecessors[&curr];
+            steps.push((mv, curr.into()));
+            curr = prev;
+        }
+        steps.reverse();
+
+        stats.elapsed = start_time.elapsed();
+        Some(Solution {
+            energy,
+            start: *self,
+            steps,
+            stats,
+        })
+    }
+
+    fn apply(&self, mv: &Move) -> Self {
+        let mut next = *self;
+        match mv.from {
+            Location::Hallway(idx) => next.hallway[idx] = None,
+            Location::Room(room, idx) => next.rooms[room.room_idx()][idx] = None,
+        }
+        match mv.to {
+            Location::Hallway(idx) => next.hallway[idx] = Some(mv.amph),
+            Location::Room(room, idx) => next.rooms[room.room_idx()][idx] = Some(mv.amph),
+        }
+        next
     }
 }
 
@@ -674,18 +909,140 @@ impl<const N: usize> std::fmt::Display for Burrow<N> {
     }
 }
 
+impl<const N: usize> std::fmt::Display for Solution<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Starting configuration:{}", self.start)?;
+        for (mv, burrow) in &self.steps {
+            writeln!(f)?;
+            writeln!(f, "{}:{}", mv, burrow)?;
+        }
+        writeln!(f)?;
+        write!(f, "Total energy: {}", self.energy)?;
+        Ok(())
+    }
+}
+
 impl<const N: usize> std::fmt::Display for State<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Cost: {}", self.0)?;
-        writeln!(f, "State:{}", self.1)?;
+        writeln!(f, "State:{}", Burrow::from(self.1))?;
         Ok(())
     }
 }
 
+struct Game<const N: usize> {
+    burrow: Burrow<N>,
+    history: Vec<(Move, Burrow<N>)>, // Each move made along with the burrow from before it
+    energy: u32,
+}
+
+impl<const N: usize> Game<N> {
+    fn new(burrow: Burrow<N>) -> Self {
+        Self {
+            burrow,
+            history: Vec::new(),
+            energy: 0,
+        }
+    }
+
+    fn legal_moves(&self) -> Vec<(Move, Burrow<N>)> {
+        let mut moves = self.burrow.hallway_to_rooms_moves();
+        moves.extend(self.burrow.rooms_to_hallway_moves());
+        moves
+    }
+
+    fn make_move(&mut self, choice: usize) -> Option<Move> {
+        let (mv, next) = self.legal_moves().get(choice).copied()?;
+        self.history.push((mv, self.burrow));
+        self.burrow = next;
+        self.energy += mv.energy;
+        Some(mv)
+    }
+
+    fn undo(&mut self) -> Option<Move> {
+        let (mv, prev) = self.history.pop()?;
+        self.burrow = prev;
+        self.energy -= mv.energy;
+        Some(mv)
+    }
+
+    fn best_remaining(&self) -> Option<u32> {
+        self.burrow.organize_astar().map(|solution| solution.energy)
+    }
+
+    fn is_organized(&self) -> bool {
+        self.burrow == Burrow::END_STATE
+    }
+
+    fn play<R: BufRead, W: Write>(&mut self, reader: R, mut writer: W) -> std::io::Result<()> {
+        let mut lines = reader.lines();
+        loop {
+            writeln!(writer, "{}", self.burrow)?;
+            writeln!(writer, "Energy used: {}", self.energy)?;
+            if self.is_organized() == true {
+                writeln!(writer, "The amphipods are organized!")?;
+                return Ok(());
+            }
+
+            let moves = self.legal_moves();
+            if moves.is_empty() == true {
+                writeln!(
+                    writer,
+                    "No moves left, the amphipods are stuck. Try undoing a move."
+                )?;
+            }
+            for (i, (mv, _)) in moves.iter().enumerate() {
+                writeln!(writer, "{:>3}: {}", i + 1, mv)?;
+            }
+            write!(writer, "Move number, (u)ndo, (s)olve or (q)uit: ")?;
+            writer.flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            writeln!(writer)?;
+            match line.trim() {
+                "q" => return Ok(()),
+                "u" => match self.undo() {
+                    Some(mv) => writeln!(writer, "Undid: {}", mv)?,
+                    None => writeln!(writer, "Nothing to undo")?,
+                },
+                "s" => match self.best_remaining() {
+                    Some(remaining) => writeln!(
+                        writer,
+                        "At best {} more energy is needed, for {} in total",
+                        remaining,
+                        self.energy + remaining
+                    )?,
+                    None => writeln!(writer, "The amphipods can't be organized from here")?,
+                },
+                x => match x.parse::<usize>() {
+                    Ok(choice) if choice >= 1 && self.make_move(choice - 1).is_some() => (),
+                    _ => writeln!(writer, "Unknown command: {}", x)?,
+                },
+            }
+        }
+    }
+}
+
+pub fn play_amphipods<R: BufRead, W: Write>(
+    input: &str,
+    unfold: bool,
+    reader: R,
+    writer: W,
+) -> std::io::Result<()> {
+    if unfold == true {
+        Game::new(Burrow::<4>::from_string(input)).play(reader, writer)
+    } else {
+        Game::new(Burrow::<2>::from_string(input)).play(reader, writer)
+    }
+}
+
 #[aoc(day23, part1)]
 pub fn part1(input: &str) -> u32 {
     let burrow = Burrow::<2>::from_string(input);
-    let cost = burrow.organize();
+    let cost = burrow.organize_astar().expect("No solution").energy;
     assert_eq!(cost, 12240);
     cost
 }
@@ -693,7 +1050,7 @@ pub fn part1(input: &str) -> u32 {
 #[aoc(day23, part2)]
 pub fn part2(input: &str) -> u32 {
     let burrow = Burrow::<4>::from_string(input);
-    let cost = burrow.organize();
+    let cost = burrow.organize_astar().expect("No solution").energy;
     assert_eq!(cost, 44618);
     cost
 }
@@ -712,17 +1069,233 @@ mod test {
     #[test]
     fn test_organize1() {
         let burrow = Burrow::<2>::from_string(EXAMPLE_INPUT);
-        let cost = burrow.organize();
+        let cost = burrow.organize().unwrap().energy;
         assert_eq!(cost, 12521);
     }
 
     #[test]
     fn test_organize2() {
         let burrow = Burrow::<4>::from_string(EXAMPLE_INPUT);
-        let cost = burrow.organize();
+        let cost = burrow.organize().unwrap().energy;
         assert_eq!(cost, 44169);
     }
 
+    #[test]
+    fn test_organize_astar() {
+        let burrow = Burrow::<2>::from_string(EXAMPLE_INPUT);
+        let dijkstra = burrow.organize().unwrap();
+        let astar = burrow.organize_astar().unwrap();
+        assert_eq!(astar.energy, 12521);
+        assert_eq!(dijkstra.energy, astar.energy);
+        assert!(astar.stats.expanded <= dijkstra.stats.expanded);
+
+        let burrow = Burrow::<4>::from_string(EXAMPLE_INPUT);
+        let dijkstra = burrow.organize().unwrap();
+        let astar = burrow.organize_astar().unwrap();
+        assert_eq!(astar.energy, 44169);
+        assert_eq!(dijkstra.energy, astar.energy);
+        assert!(astar.stats.expanded <= dijkstra.stats.expanded);
+    }
+
+    // Compares the searches on the real burrows: cargo test --release compare_searches -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn compare_searches() {
+        let input = include_str!("../input/2021/day23.txt");
+
+        let burrow = Burrow::<2>::from_string(input);
+        let dijkstra = burrow.organize().unwrap();
+        let astar = burrow.organize_astar().unwrap();
+        println!("Part 1 Dijkstra: {}", dijkstra.stats);
+        println!("Part 1 A*:       {}", astar.stats);
+        assert_eq!(dijkstra.energy, astar.energy);
+
+        let burrow = Burrow::<4>::from_string(input);
+        let dijkstra = burrow.organize().unwrap();
+        let astar = burrow.organize_astar().unwrap();
+        println!("Part 2 Dijkstra: {}", dijkstra.stats);
+        println!("Part 2 A*:       {}", astar.stats);
+        assert_eq!(dijkstra.energy, astar.energy);
+    }
+
+    #[test]
+    fn test_min_remaining_energy() {
+        assert_eq!(Burrow::<2>::END_STATE.min_remaining_energy(), 0);
+        assert_eq!(Burrow::<4>::END_STATE.min_remaining_energy(), 0);
+
+        // Never more than the actual energy needed
+        let burrow = Burrow::<2>::from_string(EXAMPLE_INPUT);
+        assert!(burrow.min_remaining_energy() <= 12521);
+        let burrow = Burrow::<4>::from_string(EXAMPLE_INPUT);
+        assert!(burrow.min_remaining_energy() <= 44169);
+
+        let input = "\
+#############
+#.........A.#
+###.#B#C#D###
+  #A#B#C#D#
+  #########";
+        let burrow = Burrow::<2>::from_string(input);
+        assert_eq!(burrow.min_remaining_energy(), 8);
+    }
+
+    #[test]
+    fn test_packed_burrow() {
+        let burrow = Burrow::<2>::from_string(EXAMPLE_INPUT);
+        let packed = PackedBurrow::from(burrow);
+        assert_eq!(Burrow::from(packed), burrow);
+
+        let burrow = Burrow::<4>::from_string(EXAMPLE_INPUT);
+        let packed = PackedBurrow::from(burrow);
+        assert_eq!(Burrow::from(packed), burrow);
+
+        let input = "\
+#############
+#.....D.D.A.#
+###.#B#C#.###
+  #A#B#C#.#
+  #########";
+        let burrow = Burrow::<2>::from_string(input);
+        let packed = PackedBurrow::from(burrow);
+        assert_eq!(Burrow::from(packed), burrow);
+        assert_ne!(packed, PackedBurrow::from(Burrow::<2>::END_STATE));
+
+        assert_eq!(PackedBurrow::<4>::from(Burrow::new()).0, 0);
+        assert!(std::mem::size_of::<PackedBurrow<4>>() < std::mem::size_of::<Burrow<4>>());
+    }
+
+    #[test]
+    fn test_organize_moves() {
+        let burrow = Burrow::<2>::from_string(EXAMPLE_INPUT);
+        let solution = burrow.organize().unwrap();
+        assert_eq!(solution.moves().map(|m| m.energy).sum::<u32>(), 12521);
+
+        // Replaying the moves should pass through each recorded burrow and finish organized
+        let mut curr = solution.start;
+        for (mv, next) in &solution.steps {
+            curr = curr.apply(mv);
+            assert_eq!(curr, *next);
+        }
+        assert_eq!(curr, Burrow::END_STATE);
+    }
+
+    #[test]
+    fn test_organize_steps() {
+        // The worked example takes a different route for the same energy. This is the one the search settles on.
+        use Amphipod::*;
+        use Location::*;
+        let burrow = Burrow::<2>::from_string(EXAMPLE_INPUT);
+        let solution = burrow.organize().unwrap();
+        let expected = [
+            (D, Room(D, 0), Hallway(5), 2000),
+            (A, Room(D, 1), Hallway(1), 9),
+            (D, Hallway(5), Room(D, 1), 3000),
+            (B, Room(C, 0), Hallway(2), 40),
+            (C, Room(B, 0), Hallway(3), 200),
+            (C, Hallway(3), Room(C, 0), 200),
+            (D, Room(B, 1), Hallway(3), 3000),
+            (B, Hallway(2), Room(B, 1), 30),
+            (D, Hallway(3), Room(D, 0), 4000),
+            (B, Room(A, 0), Hallway(2), 20),
+            (A, Hallway(1), Room(A, 0), 2),
+            (B, Hallway(2), Room(B, 0), 20),
+        ];
+        let moves: Vec<Move> = expected
+            .iter()
+            .map(|&(amph, from, to, energy)| Move {
+                amph,
+                from,
+                to,
+                energy,
+            })
+            .collect();
+        assert_eq!(solution.moves().copied().collect::<Vec<_>>(), moves);
+        assert_eq!(
+            solution.moves().map(|m| m.energy).sum::<u32>(),
+            solution.energy
+        );
+
+        let after = |input: &str| Burrow::<2>::from_string(input);
+        assert_eq!(
+            solution.steps[2].1,
+            after(
+                "\
+#############
+#.A.........#
+###B#C#B#.###
+  #A#D#C#D#
+  #########"
+            )
+        );
+        assert_eq!(
+            solution.steps[7].1,
+            after(
+                "\
+#############
+#.A..D......#
+###B#.#C#.###
+  #A#B#C#D#
+  #########"
+            )
+        );
+        assert_eq!(solution.steps[11].1, Burrow::END_STATE);
+    }
+
+    #[test]
+    fn test_game() {
+        let mut game = Game::new(Burrow::<2>::from_string(EXAMPLE_INPUT));
+        assert_eq!(game.best_remaining(), Some(12521));
+        assert_eq!(game.undo(), None);
+
+        // Play the first move of the puzzle's worked example
+        let choice = game
+            .legal_moves()
+            .iter()
+            .position(|(mv, _)| {
+                mv.from == Location::Room(Amphipod::C, 0) && mv.to == Location::Hallway(2)
+            })
+            .unwrap();
+        let mv = game.make_move(choice).unwrap();
+        assert_eq!(mv.energy, 40);
+        assert_eq!(game.energy, 40);
+        assert_eq!(game.best_remaining(), Some(12481));
+
+        assert_eq!(game.undo(), Some(mv));
+        assert_eq!(game.energy, 0);
+        assert_eq!(game.burrow, Burrow::<2>::from_string(EXAMPLE_INPUT));
+        assert_eq!(game.make_move(1000), None);
+
+        // Two amphipods blocking each other in the hallway can never be organized
+        let input = "\
+#############
+#.....D.A...#
+###.#B#C#.###
+  #A#B#C#D#
+  #########";
+        let game = Game::new(Burrow::<2>::from_string(input));
+        assert_eq!(game.best_remaining(), None);
+    }
+
+    #[test]
+    fn test_play() {
+        let input = "\
+#############
+#.........A.#
+###.#B#C#D###
+  #A#B#C#D#
+  #########";
+        let commands = "s\nu\nx\n1\n";
+        let mut output = Vec::new();
+        play_amphipods(input, false, commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("1: A moves from hallway 5 to room A position 0, using 8 energy"));
+        assert!(output.contains("At best 8 more energy is needed, for 8 in total"));
+        assert!(output.contains("Nothing to undo"));
+        assert!(output.contains("Unknown command: x"));
+        assert!(output.contains("Energy used: 8"));
+        assert!(output.ends_with("The amphipods are organized!\n"));
+    }
+
     #[test]
     fn test_is_room_ready() {
         let burrow = Burrow::<2>::from_string(EXAMPLE_INPUT);
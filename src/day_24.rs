@@ -61,7 +61,7 @@
     What is the smallest model number accepted by MONAD?
 */
 
-use crate::common::signed;
+use crate::common::{fill_digits, from_digits, signed, to_digits_padded};
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -71,7 +71,9 @@ use nom::{
     sequence::{preceded, separated_pair},
     IResult,
 };
+use std::collections::HashSet;
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Reg {
     W,
     X,
@@ -80,6 +82,17 @@ pub enum Reg {
 }
 
 impl Reg {
+    const ALL: [Self; 4] = [Self::W, Self::X, Self::Y, Self::Z];
+
+    fn index(&self) -> usize {
+        match self {
+            Self::W => 0,
+            Self::X => 1,
+            Self::Y => 2,
+            Self::Z => 3,
+        }
+    }
+
     fn from_char(c: char) -> Self {
         match c {
             'w' => Self::W,
@@ -96,6 +109,7 @@ impl Reg {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Operand {
     Reg(Reg),
     Num(i64),
@@ -108,6 +122,7 @@ impl Operand {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Op {
     Input(Reg),
     Add(Reg, Operand),
@@ -115,6 +130,8 @@ pub enum Op {
     Div(Reg, Operand),
     Mod(Reg, Operand),
     Eql(Reg, Operand),
+    Set(Reg, i64),     // Not part of the puzzle's ALU, but the optimizer makes them
+    Neq(Reg, Operand), // Not part of the puzzle's ALU, but the optimizer makes them
 }
 
 impl Op {
@@ -144,6 +161,11 @@ impl Op {
                 "div" => Self::Div(reg, operand.unwrap()),
                 "mod" => Self::Mod(reg, operand.unwrap()),
                 "eql" => Self::Eql(reg, operand.unwrap()),
+                "set" => match operand.unwrap() {
+                    Operand::Num(n) => Self::Set(reg, n),
+                    Operand::Reg(_) => panic!("Can only set a register to a number"),
+                },
+                "neq" => Self::Neq(reg, operand.unwrap()),
                 _ => panic!("Invalid op: \"{}\"", kind),
             };
             output.push(me);
@@ -191,6 +213,19 @@ impl Op {
                         |(r, o)| Self::Eql(r, o),
                     ),
                 ),
+                preceded(
+                    tag("set "),
+                    map(separated_pair(Reg::parser, char(' '), signed), |(r, n)| {
+                        Self::Set(r, n)
+                    }),
+                ),
+                preceded(
+                    tag("neq "),
+                    map(
+                        separated_pair(Reg::parser, char(' '), Operand::parser),
+                        |(r, o)| Self::Neq(r, o),
+                    ),
+                ),
             )),
         )(input)?;
 
@@ -198,130 +233,986 @@ impl Op {
     }
 }
 
+impl std::fmt::Display for Reg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let c = match self {
+            Self::W => 'w',
+            Self::X => 'x',
+            Self::Y => 'y',
+            Self::Z => 'z',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reg(r) => write!(f, "{}", r),
+            Self::Num(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input(r) => write!(f, "inp {}", r),
+            Self::Add(r, o) => write!(f, "add {} {}", r, o),
+            Self::Mul(r, o) => write!(f, "mul {} {}", r, o),
+            Self::Div(r, o) => write!(f, "div {} {}", r, o),
+            Self::Mod(r, o) => write!(f, "mod {} {}", r, o),
+            Self::Eql(r, o) => write!(f, "eql {} {}", r, o),
+            Self::Set(r, n) => write!(f, "set {} {}", r, n),
+            Self::Neq(r, o) => write!(f, "neq {} {}", r, o),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FaultKind {
+    DivideByZero,
+    NegativeModulo, // Either side of a mod is out of range
+    MissingInput,
+    Overflow,
+}
+
+// Which instruction crashed the ALU, and why
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Fault {
+    index: usize,
+    kind: FaultKind,
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            FaultKind::DivideByZero => "divide by zero",
+            FaultKind::NegativeModulo => "mod with a negative value or a modulus of zero or less",
+            FaultKind::MissingInput => "ran out of input",
+            FaultKind::Overflow => "overflow",
+        };
+        write!(f, "Instruction {}: {}", self.index, reason)
+    }
+}
+
 struct Alu {
     regs: [i64; 4],
+    trace: Option<Vec<[i64; 4]>>, // Registers after each instruction, in w, x, y, z order
 }
 
 impl Alu {
     fn new() -> Self {
-        Self { regs: [0; 4] }
+        Self {
+            regs: [0; 4],
+            trace: None,
+        }
     }
 
-    fn reg(&self, reg: &Reg) -> i64 {
-        match reg {
-            Reg::W => self.regs[0],
-            Reg::X => self.regs[1],
-            Reg::Y => self.regs[2],
-            Reg::Z => self.regs[3],
+    fn with_trace() -> Self {
+        Self {
+            regs: [0; 4],
+            trace: Some(Vec::new()),
         }
     }
 
+    fn reg(&self, reg: &Reg) -> i64 {
+        self.regs[reg.index()]
+    }
+
     fn reg_mut(&mut self, reg: &Reg) -> &mut i64 {
-        match reg {
-            Reg::W => &mut self.regs[0],
-            Reg::X => &mut self.regs[1],
-            Reg::Y => &mut self.regs[2],
-            Reg::Z => &mut self.regs[3],
+        &mut self.regs[reg.index()]
+    }
+
+    fn operand(&self, o: &Operand) -> i64 {
+        match o {
+            Operand::Reg(r) => self.reg(r),
+            Operand::Num(num) => *num,
         }
     }
 
-    fn execute<I>(&mut self, ops: &[Op], mut input: I)
+    fn trace(&self) -> &[[i64; 4]] {
+        self.trace.as_deref().unwrap_or(&[])
+    }
+
+    fn execute<I>(&mut self, ops: &[Op], mut input: I) -> Result<(), Fault>
     where
         I: Iterator<Item = i64>,
     {
-        for op in ops {
-            match op {
-                Op::Input(r) => {
-                    let a = self.reg_mut(r);
-                    *a = input.next().unwrap();
-                }
+        for (index, op) in ops.iter().enumerate() {
+            let fault = |kind| Fault { index, kind };
+            let (r, value) = match op {
+                Op::Input(r) => (r, input.next().ok_or(fault(FaultKind::MissingInput))?),
                 Op::Add(r, o) => {
-                    let b = match o {
-                        Operand::Reg(or) => self.reg(or),
-                        Operand::Num(num) => *num,
-                    };
-                    let a = self.reg_mut(r);
-                    *a += b;
+                    let value = self.reg(r).checked_add(self.operand(o));
+                    (r, value.ok_or(fault(FaultKind::Overflow))?)
                 }
                 Op::Mul(r, o) => {
-                    let b = match o {
-                        Operand::Reg(or) => self.reg(or),
-                        Operand::Num(num) => *num,
-                    };
-                    let a = self.reg_mut(r);
-                    *a *= b;
+                    let value = self.reg(r).checked_mul(self.operand(o));
+                    (r, value.ok_or(fault(FaultKind::Overflow))?)
                 }
                 Op::Div(r, o) => {
-                    let b = match o {
-                        Operand::Reg(or) => self.reg(or),
-                        Operand::Num(num) => *num,
-                    };
-                    let a = self.reg_mut(r);
-                    assert_ne!(b, 0);
-                    *a /= b;
+                    let b = self.operand(o);
+                    if b == 0 {
+                        return Err(fault(FaultKind::DivideByZero));
+                    }
+                    // Rust division already truncates toward zero, like the ALU
+                    let value = self.reg(r).checked_div(b);
+                    (r, value.ok_or(fault(FaultKind::Overflow))?)
                 }
                 Op::Mod(r, o) => {
-                    let b = match o {
-                        Operand::Reg(or) => self.reg(or),
-                        Operand::Num(num) => *num,
-                    };
-                    let a = self.reg_mut(r);
-                    assert!(*a >= 0);
-                    assert!(b > 0);
-                    *a %= b;
+                    let a = self.reg(r);
+                    let b = self.operand(o);
+                    if a < 0 || b <= 0 {
+                        return Err(fault(FaultKind::NegativeModulo));
+                    }
+                    (r, a % b)
                 }
                 Op::Eql(r, o) => {
-                    let b = match o {
-                        Operand::Reg(or) => self.reg(or),
-                        Operand::Num(num) => *num,
+                    let value = if self.reg(r) == self.operand(o) { 1 } else { 0 };
+                    (r, value)
+                }
+                Op::Set(r, n) => (r, *n),
+                Op::Neq(r, o) => {
+                    let value = if self.reg(r) != self.operand(o) { 1 } else { 0 };
+                    (r, value)
+                }
+            };
+            *self.reg_mut(r) = value;
+
+            let regs = self.regs;
+            if let Some(trace) = &mut self.trace {
+                trace.push(regs);
+            }
+        }
+        Ok(())
+    }
+}
+
+const MODEL_NUMBER_LEN: usize = 14;
+
+fn monad_is_valid(ops: &[Op], n: i64) -> bool {
+    let mut alu = Alu::new();
+    let digits = to_digits_padded(n, 10, MODEL_NUMBER_LEN).expect("Model numbers have 14 digits");
+    alu.execute(ops, digits.into_iter()).is_ok() && alu.reg(&Reg::Z) == 0
+}
+
+// A range of values a register might hold, as far as the analysis can tell. The ends saturate, so i64::MIN and
+// i64::MAX stand in for anything beyond them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Interval {
+    min: i64,
+    max: i64,
+}
+
+impl Interval {
+    const ALL: Self = Self {
+        min: i64::MIN,
+        max: i64::MAX,
+    };
+
+    fn new(min: i64, max: i64) -> Self {
+        assert!(min <= max);
+        Self { min, max }
+    }
+
+    fn exactly(n: i64) -> Self {
+        Self::new(n, n)
+    }
+
+    fn from_values(values: &[i64]) -> Self {
+        Self::new(*values.iter().min().unwrap(), *values.iter().max().unwrap())
+    }
+
+    fn constant(&self) -> Option<i64> {
+        if self.min == self.max {
+            Some(self.min)
+        } else {
+            None
+        }
+    }
+
+    fn contains(&self, n: i64) -> bool {
+        self.min <= n && n <= self.max
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.min <= other.max && other.min <= self.max
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+}
+
+// What an instruction could do, given the ranges of the registers before it
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Effect {
+    before: [Interval; 4],
+    result: Interval, // The destination register afterwards, if the instruction didn't fault
+    may_fault: bool,
+}
+
+fn operand_interval(regs: &[Interval; 4], o: &Operand) -> Interval {
+    match o {
+        Operand::Reg(r) => regs[r.index()],
+        Operand::Num(n) => Interval::exactly(*n),
+    }
+}
+
+fn op_effect(op: &Op, regs: &[Interval; 4], inputs: Interval) -> Effect {
+    let (result, may_fault) = match op {
+        Op::Input(_) => (inputs, false),
+        Op::Set(_, n) => (Interval::exactly(*n), false),
+        Op::Add(r, o)
+        | Op::Mul(r, o)
+        | Op::Div(r, o)
+        | Op::Mod(r, o)
+        | Op::Eql(r, o)
+        | Op::Neq(r, o) => {
+            let a = regs[r.index()];
+            let b = operand_interval(regs, o);
+            match op {
+                Op::Add(..) => {
+                    // The ALU faults on overflow, so if either end could overflow then so could the instruction
+                    let overflows =
+                        a.min.checked_add(b.min).is_none() || a.max.checked_add(b.max).is_none();
+                    (
+                        Interval::new(a.min.saturating_add(b.min), a.max.saturating_add(b.max)),
+                        overflows,
+                    )
+                }
+                Op::Mul(..) => {
+                    let corners = [a.min, a.max].map(|x| [b.min, b.max].map(|y| x.checked_mul(y)));
+                    let overflows = corners.as_flattened().iter().any(|c| c.is_none());
+                    let corners =
+                        [a.min, a.max].map(|x| [b.min, b.max].map(|y| x.saturating_mul(y)));
+                    (Interval::from_values(corners.as_flattened()), overflows)
+                }
+                Op::Div(..) => {
+                    if b.contains(0) {
+                        (Interval::ALL, true)
+                    } else {
+                        // Truncating division only has its extremes at the corners when b doesn't cross zero
+                        let corners =
+                            [a.min, a.max].map(|x| [b.min, b.max].map(|y| x.saturating_div(y)));
+                        let overflows = a.contains(i64::MIN) && b.contains(-1);
+                        (Interval::from_values(corners.as_flattened()), overflows)
+                    }
+                }
+                Op::Mod(..) => {
+                    let may_fault = a.min < 0 || b.min <= 0;
+                    if may_fault == false && a.max < b.min {
+                        (a, false)
+                    } else {
+                        let max = a.max.min(b.max.saturating_sub(1)).max(0);
+                        (Interval::new(0, max), may_fault)
+                    }
+                }
+                _ => {
+                    let equal = match (a.constant(), b.constant()) {
+                        (Some(x), Some(y)) => Some(x == y),
+                        _ if a.overlaps(&b) == false => Some(false),
+                        _ => None,
                     };
-                    let a = self.reg_mut(r);
-                    *a = if *a == b { 1 } else { 0 };
+                    let is_eql = matches!(op, Op::Eql(..));
+                    match equal {
+                        Some(equal) => (
+                            Interval::exactly(if equal == is_eql { 1 } else { 0 }),
+                            false,
+                        ),
+                        None => (Interval::new(0, 1), false),
+                    }
                 }
             }
         }
+    };
+    Effect {
+        before: *regs,
+        result,
+        may_fault,
     }
 }
 
-fn digits_from_n(mut n: i64) -> Vec<i64> {
-    assert!(n > 0);
+// Run the program over ranges instead of values. Every register starts at 0 and every input is somewhere in 'inputs'.
+fn analyze(ops: &[Op], inputs: Interval) -> Vec<Effect> {
+    analyze_from(ops, [Interval::exactly(0); 4], inputs)
+}
 
-    let mut digits = Vec::new();
-    while n > 0 {
-        let d = n % 10;
-        n /= 10;
-        digits.push(d);
+fn analyze_from(ops: &[Op], mut regs: [Interval; 4], inputs: Interval) -> Vec<Effect> {
+    let mut effects = Vec::with_capacity(ops.len());
+    for op in ops {
+        let effect = op_effect(op, &regs, inputs);
+        regs[op_dest(op).index()] = effect.result;
+        effects.push(effect);
     }
-    digits.reverse();
-    assert_eq!(digits.len(), 14);
-    digits
+    effects
 }
 
-fn digits_to_n(digits: [i64; 14]) -> i64 {
-    let s = format!(
-        "{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
-        digits[0],
-        digits[1],
-        digits[2],
-        digits[3],
-        digits[4],
-        digits[5],
-        digits[6],
-        digits[7],
-        digits[8],
-        digits[9],
-        digits[10],
-        digits[11],
-        digits[12],
-        digits[13]
-    );
-    s.parse().unwrap()
+fn op_dest(op: &Op) -> Reg {
+    match op {
+        Op::Input(r)
+        | Op::Set(r, _)
+        | Op::Add(r, _)
+        | Op::Mul(r, _)
+        | Op::Div(r, _)
+        | Op::Mod(r, _)
+        | Op::Eql(r, _)
+        | Op::Neq(r, _) => *r,
+    }
 }
 
-fn monad_is_valid(ops: &[Op], n: i64) -> bool {
-    let mut alu = Alu::new();
-    alu.execute(ops, digits_from_n(n).into_iter());
-    alu.reg(&Reg::Z) == 0
+// The registers an instruction reads
+fn op_sources(op: &Op) -> Vec<Reg> {
+    match op {
+        Op::Input(_) | Op::Set(..) => vec![],
+        Op::Add(r, o)
+        | Op::Mul(r, o)
+        | Op::Div(r, o)
+        | Op::Mod(r, o)
+        | Op::Eql(r, o)
+        | Op::Neq(r, o) => match o {
+            Operand::Reg(src) => vec![*r, *src],
+            Operand::Num(_) => vec![*r],
+        },
+    }
+}
+
+fn fold_constants(ops: &[Op], inputs: Interval) -> Vec<Op> {
+    let mut output = Vec::with_capacity(ops.len());
+    for (op, effect) in ops.iter().zip(analyze(ops, inputs)) {
+        let dest = op_dest(op);
+        if matches!(op, Op::Input(_)) == false && effect.may_fault == false {
+            if let Some(n) = effect.result.constant() {
+                // Nothing to do if it already holds that value
+                if effect.before[dest.index()] != Interval::exactly(n) {
+                    output.push(Op::Set(dest, n));
+                }
+                continue;
+            }
+        }
+
+        // Registers known to hold a single value can be replaced by that value
+        let fold = |o: &Operand| match operand_interval(&effect.before, o).constant() {
+            Some(n) => Operand::Num(n),
+            None => *o,
+        };
+        let op = match op {
+            Op::Add(r, o) => Op::Add(*r, fold(o)),
+            Op::Mul(r, o) => Op::Mul(*r, fold(o)),
+            Op::Div(r, o) => Op::Div(*r, fold(o)),
+            Op::Mod(r, o) => Op::Mod(*r, fold(o)),
+            Op::Eql(r, o) => Op::Eql(*r, fold(o)),
+            Op::Neq(r, o) => Op::Neq(*r, fold(o)),
+            other => *other,
+        };
+
+        let unchanged = effect.may_fault == false && effect.result == effect.before[dest.index()];
+        let identity = match op {
+            Op::Add(_, Operand::Num(0))
+            | Op::Mul(_, Operand::Num(1))
+            | Op::Div(_, Operand::Num(1)) => true,
+            Op::Mod(..) => unchanged,
+            _ => false,
+        };
+        if identity == false {
+            output.push(op);
+        }
+    }
+    output
+}
+
+// 'eql a b' followed by 'eql a 0' is just 'a != b'
+fn fuse_comparisons(ops: &[Op]) -> Vec<Op> {
+    let mut output: Vec<Op> = Vec::with_capacity(ops.len());
+    for op in ops {
+        match (output.last(), op) {
+            (Some(Op::Eql(r1, o)), Op::Eql(r2, Operand::Num(0))) if r1 == r2 => {
+                let fused = Op::Neq(*r1, *o);
+                *output.last_mut().unwrap() = fused;
+            }
+            (Some(Op::Neq(r1, o)), Op::Eql(r2, Operand::Num(0))) if r1 == r2 => {
+                let fused = Op::Eql(*r1, *o);
+                *output.last_mut().unwrap() = fused;
+            }
+            _ => output.push(*op),
+        }
+    }
+    output
+}
+
+// The registers whose values are still needed before each instruction, and at the end
+fn liveness(ops: &[Op], outputs: &[Reg]) -> Vec<[bool; 4]> {
+    let mut live = [false; 4];
+    for r in outputs {
+        live[r.index()] = true;
+    }
+    let mut result = vec![live; ops.len() + 1];
+    for (i, op) in ops.iter().enumerate().rev() {
+        live[op_dest(op).index()] = false;
+        for src in op_sources(op) {
+            live[src.index()] = true;
+        }
+        result[i] = live;
+    }
+    result
+}
+
+// Drop anything that writes a register nobody reads again. Inputs always stay, since they decide which input the
+// later ones get, and so does anything that could fault.
+fn eliminate_dead_stores(ops: &[Op], inputs: Interval, outputs: &[Reg]) -> Vec<Op> {
+    let effects = analyze(ops, inputs);
+    let mut live = [false; 4];
+    for r in outputs {
+        live[r.index()] = true;
+    }
+    let mut output = Vec::with_capacity(ops.len());
+    for (op, effect) in ops.iter().zip(effects).rev() {
+        let dest = op_dest(op);
+        if live[dest.index()] == false
+            && matches!(op, Op::Input(_)) == false
+            && effect.may_fault == false
+        {
+            continue;
+        }
+        live[dest.index()] = false;
+        for src in op_sources(op) {
+            live[src.index()] = true;
+        }
+        output.push(*op);
+    }
+    output.reverse();
+    output
+}
+
+// Simplify the program, assuming every input falls in 'inputs' and only the 'outputs' registers matter at the end.
+// The result gives the same outputs, and faults for the same inputs, for any input in range.
+fn optimize(ops: &[Op], inputs: Interval, outputs: &[Reg]) -> Vec<Op> {
+    let mut ops = ops.to_vec();
+    loop {
+        let folded = fold_constants(&ops, inputs);
+        let fused = fuse_comparisons(&folded);
+        let next = eliminate_dead_stores(&fused, inputs, outputs);
+        if next == ops {
+            return next;
+        }
+        ops = next;
+    }
+}
+
+// A symbolic value for the decompiler. Comparisons only show up as the condition of an If.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Num(i64),
+    Reg(Reg), // The register's value at the start of the block
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Mod(Box<Expr>, Box<Expr>),
+    Eql(Box<Expr>, Box<Expr>),
+    Neq(Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn boolean(cond: Expr) -> Self {
+        Self::If(
+            Box::new(cond),
+            Box::new(Self::Num(1)),
+            Box::new(Self::Num(0)),
+        )
+    }
+
+    fn select(cond: Expr, a: Expr, b: Expr) -> Self {
+        match (cond, a, b) {
+            (_, a, b) if a == b => a,
+            // Flip the condition rather than show 'if c { 0 } else { 1 }'
+            (Self::Eql(x, y), Self::Num(0), Self::Num(1)) => Self::boolean(Self::Neq(x, y)),
+            (Self::Neq(x, y), Self::Num(0), Self::Num(1)) => Self::boolean(Self::Eql(x, y)),
+            (cond, a, b) => Self::If(Box::new(cond), Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn add(a: Expr, b: Expr) -> Self {
+        match (a, b) {
+            // Leave anything that would overflow for the ALU to fault on
+            (Self::Num(x), Self::Num(y)) => match x.checked_add(y) {
+                Some(n) => Self::Num(n),
+                None => Self::Add(Box::new(Self::Num(x)), Box::new(Self::Num(y))),
+            },
+            (a, Self::Num(0)) | (Self::Num(0), a) => a,
+            (Self::Num(x), b) => Self::add(b, Self::Num(x)),
+            (Self::Add(a, k1), Self::Num(k2)) if matches!(*k1, Self::Num(k1) if k1.checked_add(k2).is_some()) => {
+                Self::add(*a, Self::add(*k1, Self::Num(k2)))
+            }
+            (a, Self::Add(b, k)) if matches!(*k, Self::Num(_)) => Self::add(Self::add(a, *b), *k),
+            (Self::If(c1, a1, b1), Self::If(c2, a2, b2)) if c1 == c2 => {
+                Self::select(*c1, Self::add(*a1, *a2), Self::add(*b1, *b2))
+            }
+            (Self::If(c, a, b), e) | (e, Self::If(c, a, b)) => {
+                Self::select(*c, Self::add(*a, e.clone()), Self::add(*b, e))
+            }
+            (a, b) => Self::Add(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn mul(a: Expr, b: Expr) -> Self {
+        match (a, b) {
+            (Self::Num(x), Self::Num(y)) => match x.checked_mul(y) {
+                Some(n) => Self::Num(n),
+                None => Self::Mul(Box::new(Self::Num(x)), Box::new(Self::Num(y))),
+            },
+            (_, Self::Num(0)) | (Self::Num(0), _) => Self::Num(0),
+            (a, Self::Num(1)) | (Self::Num(1), a) => a,
+            (Self::Num(x), b) => Self::mul(b, Self::Num(x)),
+            (Self::If(c, a, b), e) | (e, Self::If(c, a, b)) => {
+                Self::select(*c, Self::mul(*a, e.clone()), Self::mul(*b, e))
+            }
+            (a, b) => Self::Mul(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn div(a: Expr, b: Expr) -> Self {
+        match (a, b) {
+            (Self::Num(x), Self::Num(y)) if x.checked_div(y).is_some() => Self::Num(x / y),
+            (a, Self::Num(1)) => a,
+            (a, b) => Self::Div(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn modulo(a: Expr, b: Expr) -> Self {
+        match (a, b) {
+            (Self::Num(x), Self::Num(y)) if x >= 0 && y > 0 => Self::Num(x % y),
+            (a, b) => Self::Mod(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn compare(a: Expr, b: Expr, equal: bool) -> Self {
+        let truth = |same: bool| Self::Num(if same == equal { 1 } else { 0 });
+        match (a, b) {
+            (Self::Num(x), Self::Num(y)) => truth(x == y),
+            (Self::If(c, p, q), Self::Num(k)) => match (*p, *q) {
+                (Self::Num(p), Self::Num(q)) => Self::select(*c, truth(p == k), truth(q == k)),
+                (p, q) => Self::boolean(Self::cmp(
+                    Self::If(c, Box::new(p), Box::new(q)),
+                    Self::Num(k),
+                    equal,
+                )),
+            },
+            (a, b) => Self::boolean(Self::cmp(a, b, equal)),
+        }
+    }
+
+    fn cmp(a: Expr, b: Expr, equal: bool) -> Self {
+        if equal == true {
+            Self::Eql(Box::new(a), Box::new(b))
+        } else {
+            Self::Neq(Box::new(a), Box::new(b))
+        }
+    }
+
+    fn precedence(&self) -> u32 {
+        match self {
+            Self::If(..) => 0,
+            Self::Eql(..) | Self::Neq(..) => 1,
+            Self::Add(..) => 2,
+            Self::Mul(..) | Self::Div(..) | Self::Mod(..) => 3,
+            Self::Num(_) | Self::Reg(_) => 4,
+        }
+    }
+
+    fn fmt_inner(&self, f: &mut std::fmt::Formatter<'_>, min_precedence: u32) -> std::fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "(")?;
+            self.fmt_inner(f, 0)?;
+            return write!(f, ")");
+        }
+
+        let p = self.precedence();
+        let binary = |f: &mut std::fmt::Formatter<'_>, a: &Expr, symbol: &str, b: &Expr| {
+            a.fmt_inner(f, p)?;
+            write!(f, " {} ", symbol)?;
+            b.fmt_inner(f, p + 1)
+        };
+        match self {
+            Self::Num(n) => write!(f, "{}", n),
+            Self::Reg(r) => write!(f, "{}", r),
+            Self::Add(a, b) => match **b {
+                Self::Num(n) if n < 0 => {
+                    a.fmt_inner(f, p)?;
+                    write!(f, " - {}", -n)
+                }
+                _ => binary(f, a, "+", b),
+            },
+            Self::Mul(a, b) => binary(f, a, "*", b),
+            Self::Div(a, b) => binary(f, a, "/", b),
+            Self::Mod(a, b) => binary(f, a, "%", b),
+            Self::Eql(a, b) => binary(f, a, "==", b),
+            Self::Neq(a, b) => binary(f, a, "!=", b),
+            Self::If(c, a, b) => {
+                write!(f, "if ")?;
+                c.fmt_inner(f, 1)?;
+                write!(f, " {{ ")?;
+                a.fmt_inner(f, 0)?;
+                write!(f, " }} else {{ ")?;
+                b.fmt_inner(f, 0)?;
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_inner(f, 0)
+    }
+}
+
+// Describe the program as pseudo-code, one block per input, after optimizing it with the same assumptions. Within a
+// block every register is named for its value at the start of the block, or just after the input for the one that
+// was read.
+fn decompile(ops: &[Op], inputs: Interval, outputs: &[Reg]) -> String {
+    let ops = optimize(ops, inputs, outputs);
+    let effects = analyze(&ops, inputs);
+    let live = liveness(&ops, outputs);
+
+    let mut starts: Vec<usize> = (0..ops.len())
+        .filter(|i| matches!(ops[*i], Op::Input(_)))
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+
+    let mut text = String::new();
+    for (block, &start) in starts.iter().enumerate() {
+        let end = starts.get(block + 1).copied().unwrap_or(ops.len());
+
+        // Anything the analysis pinned down is shown as its value
+        let initial = Reg::ALL.map(|r| {
+            match effects
+                .get(start)
+                .and_then(|e| e.before[r.index()].constant())
+            {
+                Some(n) => Expr::Num(n),
+                None => Expr::Reg(r),
+            }
+        });
+        let mut env = initial.clone();
+        let mut first = start;
+        let mut initial_input = None;
+        text += &format!("block {}:\n", block + 1);
+        if let Some(Op::Input(r)) = ops.get(start) {
+            text += &format!("    {} = input()\n", r);
+            env[r.index()] = Expr::Reg(*r);
+            initial_input = Some(*r);
+            first += 1;
+        }
+
+        for op in &ops[first..end] {
+            let value = |o: &Operand| match o {
+                Operand::Reg(r) => env[r.index()].clone(),
+                Operand::Num(n) => Expr::Num(*n),
+            };
+            let dest = op_dest(op);
+            let a = env[dest.index()].clone();
+            let result = match op {
+                Op::Input(_) => unreachable!("Blocks start at inputs"),
+                Op::Set(_, n) => Expr::Num(*n),
+                Op::Add(_, o) => Expr::add(a, value(o)),
+                Op::Mul(_, o) => Expr::mul(a, value(o)),
+                Op::Div(_, o) => Expr::div(a, value(o)),
+                Op::Mod(_, o) => Expr::modulo(a, value(o)),
+                Op::Eql(_, o) => Expr::compare(a, value(o), true),
+                Op::Neq(_, o) => Expr::compare(a, value(o), false),
+            };
+            env[dest.index()] = result;
+        }
+
+        // The assignments happen all at once, so several are shown together
+        let changed: Vec<Reg> = Reg::ALL
+            .into_iter()
+            .filter(|r| {
+                let unchanged = if initial_input == Some(*r) {
+                    Expr::Reg(*r)
+                } else {
+                    initial[r.index()].clone()
+                };
+                live[end][r.index()] && env[r.index()] != unchanged
+            })
+            .collect();
+        if changed.is_empty() == false {
+            let names: Vec<String> = changed.iter().map(|r| r.to_string()).collect();
+            let values: Vec<String> = changed.iter().map(|r| env[r.index()].to_string()).collect();
+            text += &format!("    {} = {}\n", names.join(", "), values.join(", "));
+        }
+    }
+    text
+}
+
+// Looks for model numbers without knowing anything about how the program is laid out, other than that it reads one
+// digit per input. After each input block only the live registers matter, so (block, registers) states that are
+// known to fail are remembered. States that can't possibly end with z = 0, judging by interval analysis of the rest
+// of the program, aren't explored at all.
+struct ModelSearch {
+    ops: Vec<Op>,
+    blocks: Vec<(usize, usize)>, // Start and end of each block. The first one starts at 0 even if that isn't an input.
+    live: Vec<[bool; 4]>,        // Before each instruction, and at the end
+    digits: Vec<i64>,            // In the order to try them
+    dead_ends: HashSet<(usize, [i64; 4])>,
+}
+
+impl ModelSearch {
+    fn new(ops: &[Op], highest: bool) -> Self {
+        let inputs = Interval::new(1, 9);
+        let ops = optimize(ops, inputs, &[Reg::Z]);
+        let live = liveness(&ops, &[Reg::Z]);
+
+        let mut starts: Vec<usize> = (0..ops.len())
+            .filter(|i| matches!(ops[*i], Op::Input(_)))
+            .collect();
+        if starts.first() != Some(&0) {
+            starts.insert(0, 0);
+        }
+        let ends = starts.iter().skip(1).copied().chain([ops.len()]);
+        let blocks = starts.iter().copied().zip(ends).collect();
+
+        let digits = if highest == true {
+            (1..=9).rev().collect()
+        } else {
+            (1..=9).collect()
+        };
+
+        Self {
+            ops,
+            blocks,
+            live,
+            digits,
+            dead_ends: HashSet::new(),
+        }
+    }
+
+    fn could_reach_zero(&self, block: usize, regs: [i64; 4]) -> bool {
+        let rest = &self.ops[self.blocks[block].0..];
+        let mut ranges = regs.map(Interval::exactly);
+        for (op, effect) in rest
+            .iter()
+            .zip(analyze_from(rest, ranges, Interval::new(1, 9)))
+        {
+            ranges[op_dest(op).index()] = effect.result;
+        }
+        ranges[Reg::Z.index()].contains(0)
+    }
+
+    // The digits that take the program from this block to the end with z = 0, if any
+    fn search(&mut self, block: usize, mut regs: [i64; 4]) -> Option<Vec<i64>> {
+        if block == self.blocks.len() {
+            return if regs[Reg::Z.index()] == 0 {
+                Some(Vec::new())
+            } else {
+                None
+            };
+        }
+
+        // Dead registers can't affect anything, so forget them to get more use out of the dead ends
+        let (start, end) = self.blocks[block];
+        for r in Reg::ALL {
+            if self.live[start][r.index()] == false {
+                regs[r.index()] = 0;
+            }
+        }
+        if self.dead_ends.contains(&(block, regs)) || self.could_reach_zero(block, regs) == false {
+            return None;
+        }
+
+        let reads_input = matches!(self.ops.get(start), Some(Op::Input(_)));
+        let choices = if reads_input == true {
+            self.digits.clone()
+        } else {
+            vec![0] // Placeholder, never read
+        };
+        for digit in choices {
+            let mut alu = Alu::new();
+            alu.regs = regs;
+            if alu
+                .execute(&self.ops[start..end], [digit].into_iter())
+                .is_err()
+            {
+                continue;
+            }
+            if let Some(mut rest) = self.search(block + 1, alu.regs) {
+                if reads_input == true {
+                    rest.insert(0, digit);
+                }
+                return Some(rest);
+            }
+        }
+
+        self.dead_ends.insert((block, regs));
+        None
+    }
+}
+
+fn search_model_number(ops: &[Op], highest: bool) -> Option<i64> {
+    let mut search = ModelSearch::new(ops, highest);
+    let digits = search.search(0, [0; 4])?;
+    Some(from_digits(&digits, 10))
+}
+
+// One ALU instruction with its operand already resolved to a register index or an immediate, so running it doesn't
+// have to look at an Operand
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instr {
+    Inp(usize),
+    Set(usize, i64),
+    AddR(usize, usize),
+    AddI(usize, i64),
+    MulR(usize, usize),
+    MulI(usize, i64),
+    DivR(usize, usize),
+    DivI(usize, i64),
+    ModR(usize, usize),
+    ModI(usize, i64),
+    EqlR(usize, usize),
+    EqlI(usize, i64),
+    NeqR(usize, usize),
+    NeqI(usize, i64),
+}
+
+// A program compiled for running many inputs. The code is split into segments that each start with an input (apart
+// from any code before the first input), so numbers that share leading digits can share the work for them.
+struct Compiled {
+    code: Vec<Instr>,
+    segments: Vec<(usize, usize)>, // segments[0] runs before any input, segments[k] reads input k - 1
+}
+
+impl Compiled {
+    fn new(ops: &[Op]) -> Self {
+        let code: Vec<Instr> = ops
+            .iter()
+            .map(|op| {
+                let i = op_dest(op).index();
+                match op {
+                    Op::Input(_) => Instr::Inp(i),
+                    Op::Set(_, n) => Instr::Set(i, *n),
+                    Op::Add(_, Operand::Reg(r)) => Instr::AddR(i, r.index()),
+                    Op::Add(_, Operand::Num(n)) => Instr::AddI(i, *n),
+                    Op::Mul(_, Operand::Reg(r)) => Instr::MulR(i, r.index()),
+                    Op::Mul(_, Operand::Num(n)) => Instr::MulI(i, *n),
+                    Op::Div(_, Operand::Reg(r)) => Instr::DivR(i, r.index()),
+                    Op::Div(_, Operand::Num(n)) => Instr::DivI(i, *n),
+                    Op::Mod(_, Operand::Reg(r)) => Instr::ModR(i, r.index()),
+                    Op::Mod(_, Operand::Num(n)) => Instr::ModI(i, *n),
+                    Op::Eql(_, Operand::Reg(r)) => Instr::EqlR(i, r.index()),
+                    Op::Eql(_, Operand::Num(n)) => Instr::EqlI(i, *n),
+                    Op::Neq(_, Operand::Reg(r)) => Instr::NeqR(i, r.index()),
+                    Op::Neq(_, Operand::Num(n)) => Instr::NeqI(i, *n),
+                }
+            })
+            .collect();
+
+        let mut starts: Vec<usize> = (0..code.len())
+            .filter(|i| matches!(code[*i], Instr::Inp(_)))
+            .collect();
+        starts.insert(0, 0);
+        let ends = starts.iter().skip(1).copied().chain([code.len()]);
+        let segments = starts.iter().copied().zip(ends).collect();
+
+        Self { code, segments }
+    }
+
+    fn inputs(&self) -> usize {
+        self.segments.len() - 1
+    }
+
+    // Faults give None, the same as the interpreter giving an error
+    fn run_segment(&self, segment: usize, regs: &mut [i64; 4], input: i64) -> Option<()> {
+        let (start, end) = self.segments[segment];
+        for instr in &self.code[start..end] {
+            match *instr {
+                Instr::Inp(a) => regs[a] = input,
+                Instr::Set(a, n) => regs[a] = n,
+                Instr::AddR(a, b) => regs[a] = regs[a].checked_add(regs[b])?,
+                Instr::AddI(a, n) => regs[a] = regs[a].checked_add(n)?,
+                Instr::MulR(a, b) => regs[a] = regs[a].checked_mul(regs[b])?,
+                Instr::MulI(a, n) => regs[a] = regs[a].checked_mul(n)?,
+                Instr::DivR(a, b) => regs[a] = regs[a].checked_div(regs[b])?,
+                Instr::DivI(a, n) => regs[a] = regs[a].checked_div(n)?,
+                Instr::ModR(a, b) => {
+                    if regs[a] < 0 || regs[b] <= 0 {
+                        return None;
+                    }
+                    regs[a] %= regs[b];
+                }
+                Instr::ModI(a, n) => {
+                    if regs[a] < 0 || n <= 0 {
+                        return None;
+                    }
+                    regs[a] %= n;
+                }
+                Instr::EqlR(a, b) => regs[a] = (regs[a] == regs[b]) as i64,
+                Instr::EqlI(a, n) => regs[a] = (regs[a] == n) as i64,
+                Instr::NeqR(a, b) => regs[a] = (regs[a] != regs[b]) as i64,
+                Instr::NeqI(a, n) => regs[a] = (regs[a] != n) as i64,
+            }
+        }
+        Some(())
+    }
+
+    fn run(&self, inputs: &[i64]) -> Option<[i64; 4]> {
+        if inputs.len() < self.inputs() {
+            return None;
+        }
+        let mut regs = [0; 4];
+        self.run_segment(0, &mut regs, 0)?;
+        for (k, input) in inputs.iter().enumerate().take(self.inputs()) {
+            self.run_segment(k + 1, &mut regs, *input)?;
+        }
+        Some(regs)
+    }
+
+    fn is_valid(&self, digits: &[i64]) -> bool {
+        matches!(self.run(digits), Some(regs) if regs[Reg::Z.index()] == 0)
+    }
+
+    // Every model number in the range that the program accepts. Numbers with a 0 digit, or with a different number
+    // of digits than the program has inputs, are skipped. The registers after each digit are kept, so moving on to
+    // the next number usually only reruns the last segment.
+    fn valid_in_range(&self, range: std::ops::RangeInclusive<i64>) -> Vec<i64> {
+        let len = self.inputs();
+        let mut valid = Vec::new();
+        let mut start = [0; 4];
+        if self.run_segment(0, &mut start, 0).is_none() {
+            return valid;
+        }
+
+        let mut states = vec![start; len + 1]; // states[k] is the registers after k digits
+        let mut prev = vec![0; len];
+        let mut ready = 0; // How many digits of 'prev' the states are good for
+        let mut digits = vec![0; len];
+        for n in range {
+            if n <= 0 || fill_digits(n, 10, &mut digits) == false || digits.contains(&0) {
+                continue;
+            }
+
+            let same = digits.iter().zip(&prev).take_while(|(a, b)| a == b).count();
+            let mut k = same.min(ready);
+            while k < len {
+                let mut regs = states[k];
+                if self.run_segment(k + 1, &mut regs, digits[k]).is_none() {
+                    break;
+                }
+                states[k + 1] = regs;
+                k += 1;
+            }
+            ready = k;
+            prev.copy_from_slice(&digits);
+
+            if k == len && states[len][Reg::Z.index()] == 0 {
+                valid.push(n);
+            }
+        }
+        valid
+    }
 }
 
 /*
@@ -429,10 +1320,14 @@ fn monad_is_valid(ops: &[Op], n: i64) -> bool {
 
     Because the above analysis gives a pre-determined answer, the implementation of the ALU above is not actually used
     except to validate the analysis and its answers.
+
+    The per-block summary at the top of this analysis can now be generated from any input with decompile(), which
+    optimizes the program first, assuming only digits 1 through 9 are entered and only z matters at the end.
+    search_model_number() finds the same answers without relying on the layout at all, and the parts check it agrees.
 */
 
 fn monad_is_valid_shortcut(n: i64) -> bool {
-    let digits = digits_from_n(n);
+    let digits = to_digits_padded(n, 10, MODEL_NUMBER_LEN).expect("Model numbers have 14 digits");
     if digits[3] != digits[2] + 6 // w4 == w3 + 6
         || digits[6] != digits[5] + 1 // w7 == w6 + 1
         || digits[7] != digits[4] + 5 // w8 == w5 + 5
@@ -464,7 +1359,10 @@ fn highest_valid() -> i64 {
     let w13 = w10 - 2;
     let w14 = w1 - 8;
 
-    digits_to_n([w1, w2, w3, w4, w5, w6, w7, w8, w9, w10, w11, w12, w13, w14])
+    from_digits(
+        &[w1, w2, w3, w4, w5, w6, w7, w8, w9, w10, w11, w12, w13, w14],
+        10,
+    )
 }
 
 fn lowest_valid() -> i64 {
@@ -483,7 +1381,10 @@ fn lowest_valid() -> i64 {
     let w13 = w10 - 2;
     let w14 = w1 - 8;
 
-    digits_to_n([w1, w2, w3, w4, w5, w6, w7, w8, w9, w10, w11, w12, w13, w14])
+    from_digits(
+        &[w1, w2, w3, w4, w5, w6, w7, w8, w9, w10, w11, w12, w13, w14],
+        10,
+    )
 }
 
 #[aoc_generator(day24)]
@@ -499,6 +1400,7 @@ pub fn part1(input: &[Op]) -> i64 {
         monad_is_valid(input, answer),
         monad_is_valid_shortcut(answer)
     );
+    assert_eq!(search_model_number(input, true), Some(answer));
     assert_eq!(answer, 99394899891971);
     answer
 }
@@ -511,6 +1413,7 @@ pub fn part2(input: &[Op]) -> i64 {
         monad_is_valid(input, answer),
         monad_is_valid_shortcut(answer)
     );
+    assert_eq!(search_model_number(input, false), Some(answer));
     assert_eq!(answer, 92171126131911);
     answer
 }
@@ -518,6 +1421,8 @@ pub fn part2(input: &[Op]) -> i64 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::common::SplitMix64;
+    use crate::common::{has_zero_digit, ConstrainedDigits};
 
     static EXAMPLE_INPUT1: &str = "\
 inp x
@@ -549,27 +1454,27 @@ mod w 2
     fn test_examples() {
         let ops = input_generator(EXAMPLE_INPUT1);
         let mut alu = Alu::new();
-        alu.execute(&ops, [123].into_iter());
+        alu.execute(&ops, [123].into_iter()).unwrap();
         assert_eq!(alu.reg(&Reg::X), -123);
 
         let ops = input_generator(EXAMPLE_INPUT1);
         let mut alu = Alu::new();
-        alu.execute(&ops, [-456].into_iter());
+        alu.execute(&ops, [-456].into_iter()).unwrap();
         assert_eq!(alu.reg(&Reg::X), 456);
 
         let ops = input_generator(EXAMPLE_INPUT2);
         let mut alu = Alu::new();
-        alu.execute(&ops, [123, 369].into_iter());
+        alu.execute(&ops, [123, 369].into_iter()).unwrap();
         assert_eq!(alu.reg(&Reg::Z), 1);
 
         let ops = input_generator(EXAMPLE_INPUT2);
         let mut alu = Alu::new();
-        alu.execute(&ops, [123, 400].into_iter());
+        alu.execute(&ops, [123, 400].into_iter()).unwrap();
         assert_eq!(alu.reg(&Reg::Z), 0);
 
         let ops = input_generator(EXAMPLE_INPUT3);
         let mut alu = Alu::new();
-        alu.execute(&ops, [0].into_iter());
+        alu.execute(&ops, [0].into_iter()).unwrap();
         assert_eq!(alu.reg(&Reg::W), 0);
         assert_eq!(alu.reg(&Reg::X), 0);
         assert_eq!(alu.reg(&Reg::Y), 0);
@@ -577,7 +1482,7 @@ mod w 2
 
         let ops = input_generator(EXAMPLE_INPUT3);
         let mut alu = Alu::new();
-        alu.execute(&ops, [5].into_iter());
+        alu.execute(&ops, [5].into_iter()).unwrap();
         assert_eq!(alu.reg(&Reg::W), 0);
         assert_eq!(alu.reg(&Reg::X), 1);
         assert_eq!(alu.reg(&Reg::Y), 0);
@@ -585,7 +1490,7 @@ mod w 2
 
         let ops = input_generator(EXAMPLE_INPUT3);
         let mut alu = Alu::new();
-        alu.execute(&ops, [0xF].into_iter());
+        alu.execute(&ops, [0xF].into_iter()).unwrap();
         assert_eq!(alu.reg(&Reg::W), 1);
         assert_eq!(alu.reg(&Reg::X), 1);
         assert_eq!(alu.reg(&Reg::Y), 1);
@@ -595,6 +1500,7 @@ mod w 2
     #[test]
     fn test_monads() {
         let ops = input_generator(include_str!("../input/2021/day24.txt"));
+        let compiled = Compiled::new(&ops);
         for w1 in (9..=9).rev() {
             let w14 = w1 - 8;
             for w2 in (2..=9).rev() {
@@ -609,10 +1515,12 @@ mod w 2
                                 let w13 = w10 - 2;
                                 for w11 in (1..=1).rev() {
                                     let w12 = w11 + 8;
-                                    let n = digits_to_n([
+                                    let digits = [
                                         w1, w2, w3, w4, w5, w6, w7, w8, w9, w10, w11, w12, w13, w14,
-                                    ]);
+                                    ];
+                                    let n = from_digits(&digits, 10);
                                     assert!(monad_is_valid(&ops, n));
+                                    assert!(compiled.is_valid(&digits));
                                     assert!(monad_is_valid_shortcut(n));
                                 }
                             }
@@ -622,4 +1530,286 @@ mod w 2
             }
         }
     }
+
+    #[test]
+    fn test_faults() {
+        let ops = input_generator(EXAMPLE_INPUT2);
+        let mut alu = Alu::new();
+        let fault = alu.execute(&ops, [3].into_iter()).unwrap_err();
+        assert_eq!(
+            fault,
+            Fault {
+                index: 1,
+                kind: FaultKind::MissingInput
+            }
+        );
+        assert_eq!(fault.to_string(), "Instruction 1: ran out of input");
+
+        let ops = input_generator("inp x\ninp y\ndiv x y\n");
+        let mut alu = Alu::new();
+        assert_eq!(
+            alu.execute(&ops, [5, 0].into_iter())
+                .map_err(|f| (f.index, f.kind)),
+            Err((2, FaultKind::DivideByZero))
+        );
+
+        // Registers keep their values from up to the fault
+        let ops = input_generator("inp x\nadd y 3\nmod x y\nadd z 1\n");
+        let mut alu = Alu::new();
+        assert_eq!(
+            alu.execute(&ops, [-7].into_iter())
+                .map_err(|f| (f.index, f.kind)),
+            Err((2, FaultKind::NegativeModulo))
+        );
+        assert_eq!(alu.regs, [0, -7, 3, 0]);
+
+        let ops = input_generator("inp x\nmod x 0\n");
+        let mut alu = Alu::new();
+        assert_eq!(
+            alu.execute(&ops, [7].into_iter()).map_err(|f| f.kind),
+            Err(FaultKind::NegativeModulo)
+        );
+
+        let ops = input_generator("inp x\nmul x x\nmul x x\nmul x x\n");
+        let mut alu = Alu::new();
+        assert_eq!(
+            alu.execute(&ops, [1000].into_iter())
+                .map_err(|f| (f.index, f.kind)),
+            Err((3, FaultKind::Overflow))
+        );
+
+        // Division truncates toward zero
+        let ops = input_generator("inp x\ndiv x 2\n");
+        let mut alu = Alu::new();
+        alu.execute(&ops, [-7].into_iter()).unwrap();
+        assert_eq!(alu.reg(&Reg::X), -3);
+    }
+
+    #[test]
+    fn test_trace() {
+        let ops = input_generator(EXAMPLE_INPUT2);
+        let mut alu = Alu::with_trace();
+        alu.execute(&ops, [123, 369].into_iter()).unwrap();
+        assert_eq!(
+            alu.trace(),
+            [
+                [0, 0, 0, 123],
+                [0, 369, 0, 123],
+                [0, 369, 0, 369],
+                [0, 369, 0, 1]
+            ]
+        );
+
+        // The trace stops at the fault
+        let ops = input_generator(EXAMPLE_INPUT3);
+        let mut alu = Alu::with_trace();
+        assert!(alu.execute(&ops, [-5].into_iter()).is_err());
+        assert_eq!(alu.trace(), [[-5, 0, 0, 0], [-5, 0, 0, -5]]);
+
+        let mut alu = Alu::new();
+        alu.execute(&ops, [5].into_iter()).unwrap();
+        assert!(alu.trace().is_empty());
+
+        let listing: Vec<String> = ops.iter().map(|op| op.to_string()).collect();
+        assert_eq!(listing.join("\n") + "\n", EXAMPLE_INPUT3);
+    }
+
+    #[test]
+    fn test_optimize() {
+        let ops = input_generator(EXAMPLE_INPUT2);
+        let optimized = optimize(&ops, Interval::ALL, &[Reg::Z]);
+        assert_eq!(
+            optimized,
+            input_generator("inp z\ninp x\nmul z 3\neql z x\n")
+        );
+
+        // Constants fold all the way through a comparison that can't succeed, and the stores in between are dead
+        let ops =
+            input_generator("inp w\nmul x 0\nadd x z\nmod x 26\nadd x 12\neql x w\neql x 0\n");
+        assert_eq!(
+            optimize(&ops, Interval::new(1, 9), &[Reg::X]),
+            [Op::Input(Reg::W), Op::Set(Reg::X, 1)]
+        );
+        assert_eq!(
+            optimize(&ops, Interval::ALL, &[Reg::X]),
+            [
+                Op::Input(Reg::W),
+                Op::Set(Reg::X, 12),
+                Op::Neq(Reg::X, Operand::Reg(Reg::W))
+            ]
+        );
+
+        // x is thrown away in the end, but squaring it can overflow on the way
+        let ops = input_generator("inp x\nmul x x\nmul x x\nmul x x\nmul x 0\n");
+        let optimized = optimize(&ops, Interval::exactly(1000), &[Reg::X]);
+        let fault = |ops: &[Op]| {
+            Alu::new()
+                .execute(ops, [1000].into_iter())
+                .unwrap_err()
+                .kind
+        };
+        assert_eq!(fault(&ops), FaultKind::Overflow);
+        assert_eq!(fault(&optimized), FaultKind::Overflow);
+        let optimized = optimize(&ops, Interval::new(-100, 100), &[Reg::X]);
+        assert_eq!(optimized, [Op::Input(Reg::X), Op::Set(Reg::X, 0)]);
+
+        // Nothing reads y, but the division could still fault unless the input rules out zero
+        let ops = input_generator("inp x\nadd y 5\ndiv y x\n");
+        assert_eq!(optimize(&ops, Interval::ALL, &[Reg::X]).len(), 3);
+        assert_eq!(
+            optimize(&ops, Interval::new(1, 9), &[Reg::X]),
+            [Op::Input(Reg::X)]
+        );
+
+        // A mod that can't change anything goes too
+        let ops = input_generator(EXAMPLE_INPUT3);
+        let optimized = optimize(&ops, Interval::new(0, 15), &Reg::ALL);
+        assert_eq!(optimized.len(), ops.len() - 1);
+        for n in 0..=15 {
+            let mut a = Alu::new();
+            let mut b = Alu::new();
+            a.execute(&ops, [n].into_iter()).unwrap();
+            b.execute(&optimized, [n].into_iter()).unwrap();
+            assert_eq!(a.regs, b.regs);
+        }
+
+        // The real thing gives the same answer for all sorts of model numbers
+        let ops = input_generator(include_str!("../input/2021/day24.txt"));
+        let optimized = optimize(&ops, Interval::new(1, 9), &[Reg::Z]);
+        assert!(optimized.len() < ops.len() * 2 / 3);
+
+        // Printing it out and reading it back gets the same program
+        let text: String = optimized.iter().map(|op| format!("{}\n", op)).collect();
+        assert!(text.contains("set ") && text.contains("neq "));
+        assert_eq!(input_generator(&text), optimized);
+        assert_eq!(Op::many_from_string(&text), optimized);
+
+        let mut rng = SplitMix64::new(24);
+        for _ in 0..1000 {
+            let digits: Vec<i64> = (0..14).map(|_| rng.range(1, 9) as i64).collect();
+            let mut a = Alu::new();
+            let mut b = Alu::new();
+            a.execute(&ops, digits.iter().copied()).unwrap();
+            b.execute(&optimized, digits.iter().copied()).unwrap();
+            assert_eq!(a.reg(&Reg::Z), b.reg(&Reg::Z));
+        }
+    }
+
+    #[test]
+    fn test_decompile() {
+        let ops = input_generator(EXAMPLE_INPUT1);
+        assert_eq!(
+            decompile(&ops, Interval::ALL, &[Reg::X]),
+            "block 1:\n    x = input()\n    x = x * -1\n"
+        );
+
+        let ops = input_generator(EXAMPLE_INPUT3);
+        assert_eq!(
+            decompile(&ops, Interval::new(0, 15), &Reg::ALL),
+            "block 1:\n    w = input()\n    w, x, y, z = w / 2 / 2 / 2, w / 2 / 2 % 2, w / 2 % 2, w % 2\n"
+        );
+
+        // Constants that would overflow are left for the ALU to fault on
+        let ops = input_generator("inp w\nadd z 9223372036854775807\nadd z 1\n");
+        assert_eq!(
+            decompile(&ops, Interval::ALL, &[Reg::Z]),
+            "block 1:\n    w = input()\n    z = 9223372036854775807 + 1\n"
+        );
+        let ops = input_generator(
+            "inp w\nadd z -9223372036854775808\ndiv z -1\nadd y 4611686018427387904\nmul y 2\n",
+        );
+        assert_eq!(
+            decompile(&ops, Interval::ALL, &[Reg::Y, Reg::Z]),
+            "block 1:\n    w = input()\n    y, z = 4611686018427387904 * 2, -9223372036854775808 / -1\n"
+        );
+
+        // Compare with the hand-written analysis below: the first block has A = 1, C = 6 and the fourth A = 26,
+        // B = -8, C = 10
+        let ops = input_generator(include_str!("../input/2021/day24.txt"));
+        let text = decompile(&ops, Interval::new(1, 9), &[Reg::Z]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 14 * 3);
+        assert_eq!(
+            lines[0..3],
+            ["block 1:", "    w = input()", "    z = w + 6"]
+        );
+        assert_eq!(lines[5], "    z = z * 26 + w + 14");
+        assert_eq!(
+            lines[11],
+            "    z = if z % 26 - 8 != w { z / 26 * 26 + w + 10 } else { z / 26 }"
+        );
+    }
+
+    #[test]
+    fn test_search_model_number() {
+        let ops = input_generator(include_str!("../input/2021/day24.txt"));
+        assert_eq!(search_model_number(&ops, true), Some(99394899891971));
+        assert_eq!(search_model_number(&ops, false), Some(92171126131911));
+
+        // Nothing like MONAD: two digits that add up to 10, with the first one bigger
+        let ops = input_generator("inp w\ninp x\nadd z w\nadd z x\neql z 10\nadd y w\nmul x -1\nadd y x\nadd y 100\ndiv y 101\nmul z y\neql z 0\n");
+        assert_eq!(search_model_number(&ops, true), Some(91));
+        assert_eq!(search_model_number(&ops, false), Some(64));
+
+        // Can't ever be valid
+        let ops = input_generator("inp w\nadd z w\n");
+        assert_eq!(search_model_number(&ops, true), None);
+
+        // Faulting inputs don't count
+        let ops = input_generator("inp w\nadd w -5\ndiv z w\n");
+        assert_eq!(search_model_number(&ops, true), Some(9));
+        assert_eq!(search_model_number(&ops, false), Some(1));
+    }
+
+    #[test]
+    fn test_compiled() {
+        let ops = input_generator(EXAMPLE_INPUT3);
+        let compiled = Compiled::new(&ops);
+        assert_eq!(compiled.inputs(), 1);
+        assert_eq!(compiled.run(&[0xB]), Some([1, 0, 1, 1]));
+        assert_eq!(compiled.run(&[-1]), None);
+        assert_eq!(compiled.run(&[]), None);
+
+        // z is 0 unless the second digit is three times the first
+        let ops = input_generator(EXAMPLE_INPUT2);
+        let valid = Compiled::new(&ops).valid_in_range(1..=99);
+        assert_eq!(valid.len(), 81 - 3);
+        assert_eq!(valid.contains(&26), false);
+        assert_eq!(valid.contains(&62), true);
+
+        // Same answers as the interpreter, before and after optimizing
+        let ops = input_generator(include_str!("../input/2021/day24.txt"));
+        let optimized = optimize(&ops, Interval::new(1, 9), &[Reg::Z]);
+        let range = 99394899891000..=99394899893999;
+        let expected: Vec<i64> = range
+            .clone()
+            .filter(|n| has_zero_digit(*n, 10) == false && monad_is_valid(&ops, *n))
+            .collect();
+        assert_eq!(expected.contains(&99394899891971), true);
+        assert_eq!(Compiled::new(&ops).valid_in_range(range.clone()), expected);
+        assert_eq!(Compiled::new(&optimized).valid_in_range(range), expected);
+
+        // Ranges that start partway through, cross a digit boundary, or are the wrong length
+        let compiled = Compiled::new(&optimized);
+        assert_eq!(
+            compiled.valid_in_range(92171126131911..=92171126131911),
+            [92171126131911]
+        );
+        assert_eq!(compiled.valid_in_range(92171126131910..=92171126131910), []);
+        assert!(compiled.valid_in_range(1..=999).is_empty());
+
+        // Counting down through just the digits 1 to 9 finds the same numbers as the batch
+        let top: Vec<Vec<i64>> = ConstrainedDigits::descending(MODEL_NUMBER_LEN, 1..=9)
+            .take(5000)
+            .collect();
+        let lowest = from_digits(top.last().unwrap(), 10);
+        let mut expected = compiled.valid_in_range(lowest..=99999999999999);
+        expected.reverse();
+        let found: Vec<i64> = top
+            .iter()
+            .filter(|d| compiled.is_valid(d))
+            .map(|d| from_digits(d, 10))
+            .collect();
+        assert_eq!(found, expected);
+    }
 }
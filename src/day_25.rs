@@ -294,9 +294,13 @@
     Only 49 stars to go.
 */
 
-use crate::common::{Point2, Range2, TileChar, TileMap};
+use crate::common::{
+    run_until_repeat, Boundary, Grid, Neighborhood, Point2, Rule, RunResult, TileChar,
+};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 enum Cuke {
     East,
     South,
@@ -326,72 +330,313 @@ impl TileChar for Cuke {
     }
 }
 
-#[derive(Clone)]
+// One herd moves forward a space wherever the space is empty. Each cell only needs to see behind and ahead of it.
+struct Herd {
+    kind: Cuke,
+    dir: Point2,
+}
+
+impl Rule for Herd {
+    type Cell = Cuke;
+
+    fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::Custom(vec![Point2::origin() - self.dir, self.dir])
+    }
+
+    fn next(&self, cell: &Cuke, neighbors: &[Cuke]) -> Cuke {
+        let (behind, ahead) = (neighbors[0], neighbors[1]);
+        if *cell == self.kind && ahead == Cuke::None {
+            Cuke::None
+        } else if *cell == Cuke::None && behind == self.kind {
+            self.kind
+        } else {
+            *cell
+        }
+    }
+}
+
+#[derive(Clone, Eq, Hash, PartialEq)]
 pub struct Floor {
-    map: TileMap<Cuke>,
-    range: Range2,
+    map: Grid<Cuke>,
 }
 
 impl Floor {
+    const EAST: Herd = Herd {
+        kind: Cuke::East,
+        dir: Point2 { x: 1, y: 0 },
+    };
+
+    const SOUTH: Herd = Herd {
+        kind: Cuke::South,
+        dir: Point2 { x: 0, y: 1 },
+    };
+
+    const STEP_LIMIT: usize = 10_000;
+
     fn from_string(input: &str) -> Self {
-        let map = TileMap::<Cuke>::from_string(input);
-        let range = map.get_range().unwrap();
-        Self { map, range }
+        let map = Grid::from_string(input, Boundary::Wrapping);
+        Self { map }
     }
 
+    // Returns true if nothing moved
     fn step(&mut self) -> bool {
-        let mut from_to: Vec<(Point2, Point2)> = Vec::new();
-
-        // First east
-        for (p, cuke) in self.map.iter() {
-            if cuke == &Cuke::East {
-                let mut adj = p + (1, 0);
-                if adj.x > self.range.x.1 {
-                    adj.x = self.range.x.0;
-                }
+        let east_moved = self.map.step(&Self::EAST);
+        let south_moved = self.map.step(&Self::SOUTH);
+        east_moved == false && south_moved == false
+    }
 
-                if matches!(self.map.get(&adj), Some(Cuke::East | Cuke::South)) == false {
-                    from_to.push((*p, adj));
-                }
+    // Keep stepping until the herds stop moving, or settle into a loop, or the step limit runs out. Every state is
+    // remembered, so a loop is caught the first time it comes round.
+    fn run(&mut self, step_limit: usize) -> RunResult {
+        run_until_repeat(self, step_limit, |floor| {
+            floor.step();
+        })
+    }
+
+    fn find_no_movement(&mut self) -> Option<u32> {
+        match self.run(Self::STEP_LIMIT) {
+            RunResult::FixedPoint(n) => Some(n as u32),
+            RunResult::Cycle { .. } | RunResult::StepLimit => None,
+        }
+    }
+}
+
+// Bit x of the output is bit x - 1 of the row, with the last bit wrapping round to the start
+fn rotate_up(row: &[u64], out: &mut [u64], width: usize) {
+    let last = width - 1;
+    let mut carry = (row[last / 64] >> (last % 64)) & 1;
+    for (o, word) in out.iter_mut().zip(row) {
+        *o = (word << 1) | carry;
+        carry = word >> 63;
+    }
+
+    // Clear whatever was shifted past the width
+    let extra = out.len() * 64 - width;
+    if extra > 0 {
+        out[out.len() - 1] &= u64::MAX >> extra;
+    }
+}
+
+// Bit x of the output is bit x + 1 of the row, with the first bit wrapping round to the end
+fn rotate_down(row: &[u64], out: &mut [u64], width: usize) {
+    for i in 0..row.len() {
+        let next = row.get(i + 1).map_or(0, |word| word << 63);
+        out[i] = (row[i] >> 1) | next;
+    }
+    let last = width - 1;
+    out[last / 64] |= (row[0] & 1) << (last % 64);
+}
+
+// The same floor with each herd as a bitset per row, so a whole row of sea cucumbers moves with a few shifts and masks
+#[derive(Clone)]
+pub struct BitFloor {
+    width: usize,
+    height: usize,
+    words: usize, // Per row
+    east: Vec<u64>,
+    south: Vec<u64>,
+    occupied: Vec<u64>, // Scratch space, so stepping doesn't allocate
+    moving: Vec<u64>,
+}
+
+impl BitFloor {
+    fn get(&self, x: usize, y: usize) -> Cuke {
+        let i = y * self.words + x / 64;
+        let bit = 1 << (x % 64);
+        if self.east[i] & bit != 0 {
+            Cuke::East
+        } else if self.south[i] & bit != 0 {
+            Cuke::South
+        } else {
+            Cuke::None
+        }
+    }
+
+    fn fill_occupied(&mut self) {
+        for i in 0..self.occupied.len() {
+            self.occupied[i] = self.east[i] | self.south[i];
+        }
+    }
+
+    fn step_east(&mut self) -> bool {
+        let mut moved = false;
+        self.fill_occupied();
+        for y in 0..self.height {
+            let row = y * self.words..(y + 1) * self.words;
+
+            // What's ahead of each sea cucumber, then which of them are free to go
+            rotate_down(
+                &self.occupied[row.clone()],
+                &mut self.moving[row.clone()],
+                self.width,
+            );
+            for i in row.clone() {
+                self.moving[i] = self.east[i] & !self.moving[i];
+                self.east[i] &= !self.moving[i];
+                moved |= self.moving[i] != 0;
+            }
+
+            // This row of occupied isn't needed any more, so it can hold where they land
+            rotate_up(
+                &self.moving[row.clone()],
+                &mut self.occupied[row.clone()],
+                self.width,
+            );
+            for i in row {
+                self.east[i] |= self.occupied[i];
             }
         }
+        moved
+    }
 
-        let east_count = from_to.len();
-        for (from, to) in &from_to {
-            self.map.remove(from);
-            self.map.insert(*to, Cuke::East);
+    fn step_south(&mut self) -> bool {
+        let mut moved = false;
+        self.fill_occupied();
+        for y in 0..self.height {
+            let below = (y + 1) % self.height;
+            for w in 0..self.words {
+                let i = y * self.words + w;
+                self.moving[i] = self.south[i] & !self.occupied[below * self.words + w];
+                moved |= self.moving[i] != 0;
+            }
         }
+        for y in 0..self.height {
+            let above = (y + self.height - 1) % self.height;
+            for w in 0..self.words {
+                let i = y * self.words + w;
+                self.south[i] =
+                    (self.south[i] & !self.moving[i]) | self.moving[above * self.words + w];
+            }
+        }
+        moved
+    }
 
-        // Then south
-        from_to.clear();
-        for (p, cuke) in self.map.iter() {
-            if cuke == &Cuke::South {
-                let mut adj = p + (0, 1);
-                if adj.y > self.range.y.1 {
-                    adj.y = self.range.y.0;
-                }
+    // Returns true if nothing moved
+    fn step(&mut self) -> bool {
+        let east_moved = self.step_east();
+        let south_moved = self.step_south();
+        east_moved == false && south_moved == false
+    }
 
-                if matches!(self.map.get(&adj), Some(Cuke::East | Cuke::South)) == false {
-                    from_to.push((*p, adj));
+    fn run(&mut self, step_limit: usize) -> RunResult {
+        run_until_repeat(self, step_limit, |floor| {
+            floor.step();
+        })
+    }
+
+    fn find_no_movement(&mut self) -> Option<u32> {
+        match self.run(Floor::STEP_LIMIT) {
+            RunResult::FixedPoint(n) => Some(n as u32),
+            RunResult::Cycle { .. } | RunResult::StepLimit => None,
+        }
+    }
+
+    // Write out every generation, like the puzzle description does, until the herds stop or loop
+    fn animate<W: Write>(
+        &mut self,
+        writer: &mut W,
+        step_limit: usize,
+    ) -> std::io::Result<RunResult> {
+        write!(writer, "Initial state:\n{}\n", self)?;
+        let mut n = 0;
+        let mut error = None;
+        let result = run_until_repeat(self, step_limit, |floor| {
+            floor.step();
+            n += 1;
+            if error.is_none() == true {
+                let plural = if n == 1 { "" } else { "s" };
+                if let Err(e) = write!(writer, "After {} step{}:\n{}\n", n, plural, floor) {
+                    error = Some(e);
                 }
             }
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(result),
         }
+    }
+}
 
-        let south_count = from_to.len();
-        for (from, to) in from_to {
-            self.map.remove(&from);
-            self.map.insert(to, Cuke::South);
+impl From<&Floor> for BitFloor {
+    fn from(floor: &Floor) -> Self {
+        let width = floor.map.width();
+        let height = floor.map.height();
+        assert!(width > 0 && height > 0, "The floor can't be empty");
+
+        let words = width.div_ceil(64);
+        let mut east = vec![0; words * height];
+        let mut south = vec![0; words * height];
+        for (p, cuke) in floor.map.iter() {
+            let (x, y) = (p.x as usize, p.y as usize);
+            let i = y * words + x / 64;
+            match cuke {
+                Cuke::East => east[i] |= 1 << (x % 64),
+                Cuke::South => south[i] |= 1 << (x % 64),
+                Cuke::None => (),
+            }
         }
 
-        east_count == 0 && south_count == 0
+        Self {
+            width,
+            height,
+            words,
+            east,
+            south,
+            occupied: vec![0; words * height],
+            moving: vec![0; words * height],
+        }
     }
+}
 
-    fn find_no_movement(&mut self) -> u32 {
-        let mut n = 1; // Start at 1 since we don't increment after the final step
-        while self.step() == false {
-            n += 1;
+// Only the sea cucumbers count, not the scratch space
+impl PartialEq for BitFloor {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.east == other.east && self.south == other.south
+    }
+}
+
+impl Eq for BitFloor {}
+
+impl Hash for BitFloor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.width.hash(state);
+        self.east.hash(state);
+        self.south.hash(state);
+    }
+}
+
+impl std::fmt::Display for BitFloor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                write!(f, "{}", self.get(x, y).to_char())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn animate_sea_cucumbers<W: Write>(
+    input: &str,
+    step_limit: usize,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let mut floor = BitFloor::from(&Floor::from_string(input));
+    match floor.animate(writer, step_limit)? {
+        RunResult::FixedPoint(n) => {
+            writeln!(writer, "The sea cucumbers stop moving on step {}.", n)
         }
-        n
+        RunResult::Cycle { start, length } => writeln!(
+            writer,
+            "The sea cucumbers go round in circles, repeating every {} steps from step {}.",
+            length, start
+        ),
+        RunResult::StepLimit => writeln!(
+            writer,
+            "The sea cucumbers are still moving after {} steps.",
+            step_limit
+        ),
     }
 }
 
@@ -402,8 +647,10 @@ pub fn input_generator(input: &str) -> Floor {
 
 #[aoc(day25, part1)]
 pub fn part1(input: &Floor) -> u32 {
-    let mut floor = input.clone();
-    let answer = floor.find_no_movement();
+    let mut floor = BitFloor::from(input);
+    let answer = floor
+        .find_no_movement()
+        .expect("The sea cucumbers never stop");
     assert_eq!(answer, 482);
     answer
 }
@@ -532,7 +779,7 @@ vvv.....>>
     fn test_find_no_movement() {
         let mut floor = input_generator(EXAMPLE_INPUT4);
         let steps = floor.find_no_movement();
-        assert_eq!(steps, 58);
+        assert_eq!(steps, Some(58));
         assert_eq!(
             floor.map.to_string().trim(),
             "\
@@ -547,4 +794,131 @@ vvv.....>>
 .>v.vv.v.."
         );
     }
+
+    #[test]
+    fn test_run() {
+        let mut floor = input_generator(EXAMPLE_INPUT4);
+        assert_eq!(floor.run(1000), RunResult::FixedPoint(58));
+
+        let mut floor = input_generator(EXAMPLE_INPUT4);
+        assert_eq!(floor.run(10), RunResult::StepLimit);
+        assert_eq!(floor.run(1000), RunResult::FixedPoint(48));
+
+        // A row with a single gap goes round forever, as does a column
+        let mut floor = input_generator(">>.>");
+        assert_eq!(
+            floor.run(1000),
+            RunResult::Cycle {
+                start: 0,
+                length: 4
+            }
+        );
+        assert_eq!(floor.map.to_string(), ">>.>\n");
+        let mut floor = input_generator("v\n.\nv");
+        assert_eq!(
+            floor.run(1000),
+            RunResult::Cycle {
+                start: 0,
+                length: 3
+            }
+        );
+        assert_eq!(floor.find_no_movement(), None);
+
+        // Both herds pass through the top right corner, taking turns
+        let mut floor = input_generator(">.v\n...\n...");
+        assert_eq!(
+            floor.run(1000),
+            RunResult::Cycle {
+                start: 0,
+                length: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_bit_floor() {
+        for input in [
+            EXAMPLE_INPUT1,
+            EXAMPLE_INPUT2,
+            EXAMPLE_INPUT3,
+            EXAMPLE_INPUT4,
+        ] {
+            let mut floor = input_generator(input);
+            let mut bits = BitFloor::from(&floor);
+            assert_eq!(bits.to_string(), floor.map.to_string());
+            for _ in 0..60 {
+                assert_eq!(bits.step(), floor.step());
+                assert_eq!(bits.to_string(), floor.map.to_string());
+            }
+        }
+
+        let mut bits = BitFloor::from(&input_generator(EXAMPLE_INPUT4));
+        assert_eq!(bits.find_no_movement(), Some(58));
+
+        // Wider than a word, so sea cucumbers have to cross between words and wrap from the last one
+        let rows: Vec<String> = (0..3)
+            .map(|y| {
+                (0..150)
+                    .map(|x| match (x + 2 * y) % 7 {
+                        0 | 3 => '>',
+                        5 => 'v',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+        let input = rows.join("\n");
+        let mut floor = input_generator(&input);
+        let mut bits = BitFloor::from(&floor);
+        for _ in 0..200 {
+            assert_eq!(bits.step(), floor.step());
+            assert_eq!(bits.to_string(), floor.map.to_string());
+        }
+
+        let mut floor = input_generator(">.v\n...\n...");
+        let mut bits = BitFloor::from(&floor);
+        assert_eq!(bits.run(1000), floor.run(1000));
+    }
+
+    #[test]
+    fn test_animate() {
+        let mut output = Vec::new();
+        animate_sea_cucumbers(EXAMPLE_INPUT1, 2, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+Initial state:
+...>>>>>...
+
+After 1 step:
+...>>>>.>..
+
+After 2 steps:
+...>>>.>.>.
+
+The sea cucumbers are still moving after 2 steps.
+"
+        );
+
+        let mut output = Vec::new();
+        animate_sea_cucumbers("..>", 10, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+Initial state:
+..>
+
+After 1 step:
+>..
+
+After 2 steps:
+.>.
+
+After 3 steps:
+..>
+
+The sea cucumbers go round in circles, repeating every 3 steps from step 0.
+"
+        );
+    }
 }
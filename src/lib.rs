@@ -1,3 +1,4 @@
+#![allow(clippy::bool_assert_comparison)]
 #![allow(clippy::bool_comparison)]
 #![allow(clippy::needless_bool)]
 #![allow(dead_code)]
@@ -35,4 +36,8 @@ mod day_23;
 mod day_24;
 mod day_25;
 
+pub use day_19::export_beacon_map;
+pub use day_23::play_amphipods;
+pub use day_25::animate_sea_cucumbers;
+
 aoc_lib! { year = 2021 }